@@ -0,0 +1,66 @@
+//! Routes across square towns of streets.
+//!
+//! Run three times each on one shared single-core machine, against the tree from
+//! before the chunked `Grid`, with tiles in a `HashMap<Coord2, Tile>`, and the
+//! tree which added it. Each figure is criterion's estimate for one run:
+//!
+//! | town      | HashMap           | Grid              |
+//! |-----------|-------------------|-------------------|
+//! | 100×100   | 3.2 to 6.1 ms     | 3.5 to 6.5 ms     |
+//! | 1000×1000 | 148 to 165 ms     | 140 to 167 ms     |
+//!
+//! The runs overlap at both sizes, so on that machine the grid made no difference
+//! to routing which stands out from the noise.
+
+#[macro_use]
+extern crate criterion;
+extern crate tilewater;
+
+use criterion::Criterion;
+use tilewater::*;
+
+/// A square town of `size` tiles, with streets every `block` tiles and a house
+/// beside each crossroads.
+fn town(size: u64, block: u64) -> Map {
+    let mut map = Map::new(Coord2 { x: size, y: size });
+    for a in 0..size {
+        for b in (2..size).step_by(block as usize) {
+            for &location in &[Coord2 { x: a, y: b }, Coord2 { x: b, y: a }] {
+                if map.can_pave(location) {
+                    map.pave(location).unwrap();
+                }
+            }
+        }
+    }
+    for x in (2..size).step_by(block as usize) {
+        for y in (2..size).step_by(block as usize) {
+            let location = Coord2 { x: x + 2, y: y + 3 };
+            if map.can_build(location, Building::House) {
+                map.build(location, Building::House).unwrap();
+            }
+        }
+    }
+    map
+}
+
+fn route_across_town(map: Map, c: &mut Criterion, name: &str) {
+    let start = Coord2 { x: 2, y: 2 };
+    let goal = *map.buildings[&Building::House].last().unwrap();
+    assert!(route(&map, start, goal) != Route::NotRouteable);
+    c.bench_function(name, move |b| b.iter(|| route(&map, start, goal)));
+}
+
+fn route_small_town(c: &mut Criterion) {
+    route_across_town(town(100, 8), c, "route across 100x100 town");
+}
+
+fn route_large_town(c: &mut Criterion) {
+    route_across_town(town(1000, 10), c, "route across 1000x1000 town");
+}
+
+criterion_group!{
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = route_small_town, route_large_town
+}
+criterion_main!(benches);
@@ -1,4 +1,5 @@
 use super::*;
+use rand::prng::XorShiftRng;
 use rand::*;
 use rayon::prelude::*;
 use std::collections::*;
@@ -7,44 +8,214 @@ use std::mem;
 use std::sync::{mpsc, Mutex};
 use uuid::Uuid;
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum AgentKind {
     Resident,
     Train,
 }
 
+impl AgentKind {
+    pub fn from_name(name: &str) -> Option<AgentKind> {
+        match name {
+            "resident" => Some(AgentKind::Resident),
+            "train" => Some(AgentKind::Train),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            AgentKind::Resident => "resident",
+            AgentKind::Train => "train",
+        }
+    }
+}
+
+/// How many residents hold a place in each building, by its origin: those living
+/// in a house or working in a factory, and those being served in a general store
+/// or saloon.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Occupancy {
+    counts: HashMap<Coord2, u64>,
+}
+
+impl Occupancy {
+    /// How many hold a place in the building at `origin`.
+    pub fn of(&self, origin: Coord2) -> u64 {
+        self.counts.get(&origin).cloned().unwrap_or(0)
+    }
+
+    /// Whether the building at `origin` has a place free, as `Map::capacity` allows.
+    pub fn has_room(&self, map: &Map, origin: Coord2) -> bool {
+        map.capacity(origin)
+            .map_or(true, |capacity| self.of(origin) < capacity)
+    }
+
+    /// Every `building` with a place free.
+    pub fn with_room(&self, map: &Map, building: Building) -> Vec<Coord2> {
+        map.buildings.get(&building).map_or(vec![], |origins| {
+            origins
+                .iter()
+                .cloned()
+                .filter(|origin| self.has_room(map, *origin))
+                .collect()
+        })
+    }
+
+    /// The closest `building` to `start_pos` with a place free, as routed.
+    pub fn closest_with_room(
+        &self,
+        map: &Map,
+        start_pos: Coord2,
+        building: Building,
+    ) -> Option<Coord2> {
+        closest_building_where(map, start_pos, building, |origin| {
+            self.has_room(map, origin)
+        })
+    }
+
+    /// Count a resident as holding each of `places`.
+    pub fn take(&mut self, places: &[Coord2]) {
+        for place in places {
+            *self.counts.entry(*place).or_insert(0) += 1;
+        }
+    }
+
+    /// Stop counting a resident as holding each of `places`.
+    pub fn give_up(&mut self, places: &[Coord2]) {
+        for place in places {
+            if let Some(count) = self.counts.get_mut(place) {
+                *count = count.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Move every building counted by `offset`, as the map is resized.
+    pub fn shift(&mut self, offset: (i64, i64)) {
+        self.counts = self
+            .counts
+            .drain()
+            .map(|(place, count)| (place.shifted(offset), count))
+            .collect();
+    }
+}
+
+/// Which of the generators seeded from the world seed a number comes from.
+const ID_STREAM: u64 = 1;
+const DECISION_STREAM: u64 = 2;
+
 pub struct Agents {
     ticks_per_unit: u64,
     ticks_this_unit: u64,
-    agents: HashMap<AgentKind, HashMap<Uuid, Agent>>,
+    /// The world seed, from which every agent's id and decisions follow.
+    seed: u64,
+    /// Updates since the world began, for seeding each tick's decisions.
+    tick: u64,
+    /// How many ids have been handed out, for seeding the next.
+    ids_issued: u64,
+    /// Kept in order, so that agents always decide in the same order.
+    agents: BTreeMap<AgentKind, BTreeMap<Uuid, Agent>>,
+    /// Changes to the map being watched, for agents to react to on each update.
+    map_events: Option<mpsc::Receiver<MapEvent>>,
+    occupancy: Occupancy,
 }
 
 impl Agents {
-    pub fn new(ticks_per_unit: u64) -> Agents {
+    /// Agents whose ids and decisions all follow from `seed`, so that the same seed
+    /// and the same changes to the map give the same city tick for tick.
+    pub fn new(ticks_per_unit: u64, seed: u64) -> Agents {
         Agents {
             ticks_per_unit: ticks_per_unit,
             ticks_this_unit: 0,
-            agents: HashMap::new(),
+            seed,
+            tick: 0,
+            ids_issued: 0,
+            agents: BTreeMap::new(),
+            map_events: None,
+            occupancy: Occupancy::default(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Updates since the world began.
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// How many residents hold a place in each building, as of the last decisions.
+    pub fn occupancy(&self) -> &Occupancy {
+        &self.occupancy
+    }
+
+    /// Let agents react to every change made to `map` from now on, as they update.
+    pub fn watch(&mut self, map: &mut Map) {
+        self.map_events = Some(map.subscribe());
+    }
+
+    /// Let every agent react to `event`, rehoming, re-targeting or evicting them.
+    pub fn handle_event(&mut self, event: &MapEvent, map: &Map) {
+        for agents_of_kind in self.agents.values_mut() {
+            for agent in agents_of_kind.values_mut() {
+                let places = agent.places();
+                agent.handle_event(event, map, &self.occupancy);
+                self.occupancy.give_up(&places);
+                self.occupancy.take(&agent.places());
+            }
         }
     }
 
-    pub fn insert(&mut self, kind: AgentKind, agent: Agent) {
+    /// Add `agent`, giving it the next id to follow from the world seed.
+    pub fn insert(&mut self, kind: AgentKind, mut agent: Agent) {
+        let mut rng = seeded_rng(&[self.seed, ID_STREAM, self.ids_issued]);
+        self.ids_issued += 1;
+        let mut bytes = [0; 16];
+        rng.fill_bytes(&mut bytes);
+        agent.state.id = Uuid::from_random_bytes(bytes);
+        self.restore(kind, agent);
+    }
+
+    /// Add `agent` as it is, keeping its id.
+    fn restore(&mut self, kind: AgentKind, agent: Agent) {
         self.agents
             .entry(kind)
-            .or_insert_with(|| HashMap::new())
+            .or_insert_with(|| BTreeMap::new())
             .insert(agent.state.id, agent);
     }
 
+    pub fn count(&self, kind: AgentKind) -> usize {
+        self.agents.get(&kind).map_or(0, |agents| agents.len())
+    }
+
+    /// Decide the next action of every agent which has finished its last one.
     pub fn decide(&mut self, map: &Map) {
+        self.occupancy = Occupancy::default();
+        for agent in self.agents.values().flat_map(|a| a.values()) {
+            self.occupancy.take(&agent.places());
+        }
+
         let mut insert_list = vec![];
+        let (seed, tick) = (self.seed, self.tick);
         for agents_of_kind in self.agents.values_mut() {
             // @TODO: Reintroduce `par_iter_mut` using `flat_map`.
             for agent in agents_of_kind.values_mut() {
-                let mut rng: Box<RngCore> = Box::new(thread_rng());
+                if !agent.ready {
+                    continue;
+                }
+                agent.ready = false;
+                let mut rng: Box<RngCore> = Box::new(agent.rng(seed, tick));
                 let agent_state_clone = agent.state.clone();
-                agent.action = agent
-                    .decider
-                    .decide_action(&agent_state_clone, map, &mut rng);
+                let places = agent.places();
+                agent.action = agent.decider.decide_action(
+                    &agent_state_clone,
+                    map,
+                    &self.occupancy,
+                    &mut rng,
+                );
+                self.occupancy.give_up(&places);
+                self.occupancy.take(&agent.places());
                 loop {
                     let mut current_action = AgentAction::Idle;
                     mem::swap(&mut agent.action, &mut current_action);
@@ -68,33 +239,60 @@ impl Agents {
             }
         }
         for insert in insert_list {
+            self.occupancy.take(&insert.places());
             self.insert(AgentKind::Resident, insert);
         }
     }
 
+    /// Move agents along. Idle agents decide again once every unit of ticks, and
+    /// moving agents as soon as they reach the next tile. Moves along roads go at
+    /// the speed of the road's class, and elsewhere at one tile per unit.
     pub fn update(&mut self, map: &Map) {
+        let events: Vec<MapEvent> = match self.map_events {
+            Some(ref map_events) => map_events.try_iter().collect(),
+            None => vec![],
+        };
+        for event in &events {
+            self.handle_event(event, map);
+        }
+
+        self.tick += 1;
         let ticks_per_unit = self.ticks_per_unit;
         self.ticks_this_unit += 1;
-        let ticks_this_unit = self.ticks_this_unit;
+        let unit_complete = self.ticks_this_unit == ticks_per_unit;
+        if unit_complete {
+            self.ticks_this_unit = 0;
+        }
         for agents_of_kind in self.agents.values_mut() {
             let dead_agent_ids: Vec<_> = agents_of_kind
                 .par_iter_mut()
                 .filter_map(|(_, agent)| match agent.action {
                     AgentAction::Dead => Some(agent.state.id),
-                    AgentAction::Idle => None,
+                    AgentAction::Idle => {
+                        agent.ready |= unit_complete;
+                        None
+                    }
                     AgentAction::Move(direction) => {
+                        let speed = map
+                            .get(agent.state.position)
+                            .and_then(Tile::as_paving)
+                            .map_or(1.0, |paving| paving.class.speed());
+                        agent.progress =
+                            (agent.progress + speed / ticks_per_unit as f64).min(1.0);
                         let direction_offset = direction.as_offset();
-                        agent.subunit_position.0 +=
-                            (direction_offset.0 as f64) / (ticks_per_unit as f64);
-                        agent.subunit_position.1 +=
-                            (direction_offset.1 as f64) / (ticks_per_unit as f64);
-                        if ticks_this_unit == ticks_per_unit {
+                        agent.subunit_position.0 = agent.state.position.x as f64
+                            + direction_offset.0 as f64 * agent.progress;
+                        agent.subunit_position.1 = agent.state.position.y as f64
+                            + direction_offset.1 as f64 * agent.progress;
+                        if agent.progress >= 1.0 {
                             agent.state.position.x =
                                 ((agent.state.position.x as i64) + direction_offset.0) as u64;
                             agent.state.position.y =
                                 ((agent.state.position.y as i64) + direction_offset.1) as u64;
                             agent.subunit_position.0 = agent.state.position.x as f64;
                             agent.subunit_position.1 = agent.state.position.y as f64;
+                            agent.progress = 0.0;
+                            agent.ready = true;
                         }
                         None
                     }
@@ -105,10 +303,18 @@ impl Agents {
                 agents_of_kind.remove(&dead_agent_id);
             }
         }
-        if self.ticks_this_unit == self.ticks_per_unit {
-            self.ticks_this_unit = 0;
-            self.decide(map);
+        self.decide(map);
+    }
+
+    /// Move every agent, and every location their deciders hold, by `offset`, as
+    /// returned by `Map::expand` or `Map::shrink`.
+    pub fn shift(&mut self, offset: (i64, i64)) {
+        for agents_of_kind in self.agents.values_mut() {
+            for agent in agents_of_kind.values_mut() {
+                agent.shift(offset);
+            }
         }
+        self.occupancy.shift(offset);
     }
 
     pub fn agent_subunit_positions(&mut self) -> HashMap<AgentKind, Vec<(f64, f64)>> {
@@ -117,6 +323,41 @@ impl Agents {
             .map(|(k, s)| (*k, s.values().map(|a| a.subunit_position).collect()))
             .collect()
     }
+
+    pub fn save(&mut self, writer: &mut SaveWriter) {
+        let count: usize = self.agents.values().map(|a| a.len()).sum();
+        writer.record(&[
+            "agents".to_string(),
+            self.ticks_per_unit.to_string(),
+            self.ticks_this_unit.to_string(),
+            self.seed.to_string(),
+            self.tick.to_string(),
+            self.ids_issued.to_string(),
+            count.to_string(),
+        ]);
+        for (kind, agents_of_kind) in &mut self.agents {
+            for agent in agents_of_kind.values_mut() {
+                agent.save(*kind, writer);
+            }
+        }
+    }
+
+    pub fn load(reader: &mut SaveReader) -> Result<Agents, SaveError> {
+        let (line, fields) = reader.expect("agents")?;
+        let mut agents = Agents::new(
+            reader.parse(line, &fields, 0)?,
+            reader.parse(line, &fields, 2)?,
+        );
+        agents.ticks_this_unit = reader.parse(line, &fields, 1)?;
+        agents.tick = reader.parse(line, &fields, 3)?;
+        agents.ids_issued = reader.parse(line, &fields, 4)?;
+        let count: usize = reader.parse(line, &fields, 5)?;
+        for _ in 0..count {
+            let (kind, agent) = Agent::load(reader)?;
+            agents.restore(kind, agent);
+        }
+        Ok(agents)
+    }
 }
 
 #[derive(Debug)]
@@ -138,22 +379,127 @@ pub struct Agent {
     state: AgentState,
     action: AgentAction,
     subunit_position: (f64, f64),
+    /// How far through its current move the agent is, from 0 to 1.
+    progress: f64,
+    /// Whether the agent has finished its last action and needs a new one.
+    ready: bool,
     decider: Box<Decider + Send + Sync>,
 }
 
 impl Agent {
+    /// An agent without an id until it is added to `Agents`.
     pub fn new(position: Coord2, decider: Box<Decider + Send + Sync>) -> Agent {
         Agent {
             state: AgentState {
-                id: Uuid::new_v4(),
+                id: Uuid::nil(),
                 position: position,
             },
             // @TODO: Decide new action on instantiation or not?
             action: AgentAction::Idle,
             subunit_position: (position.x as f64, position.y as f64),
+            progress: 0.0,
+            ready: false,
             decider: decider,
         }
     }
+
+    pub fn shift(&mut self, offset: (i64, i64)) {
+        self.state.position = self.state.position.shifted(offset);
+        self.subunit_position.0 += offset.0 as f64;
+        self.subunit_position.1 += offset.1 as f64;
+        self.decider.shift(offset);
+    }
+
+    /// The generator for this agent's decision on `tick`, in a world seeded with
+    /// `seed`. Each agent draws from its own, so that one deciding differently
+    /// leaves the others' decisions as they were.
+    fn rng(&self, seed: u64, tick: u64) -> XorShiftRng {
+        let id = self.state.id.as_bytes();
+        let word = |bytes: &[u8]| bytes.iter().fold(0, |w, b| w << 8 | *b as u64);
+        seeded_rng(&[seed, DECISION_STREAM, word(&id[..8]), word(&id[8..]), tick])
+    }
+
+    /// The origins of the buildings this agent holds a place in.
+    pub fn places(&self) -> Vec<Coord2> {
+        self.decider.places(&self.state)
+    }
+
+    /// Let the decider react to `event`, replacing the current action if it says to.
+    pub fn handle_event(&mut self, event: &MapEvent, map: &Map, occupancy: &Occupancy) {
+        if let Some(action) = self
+            .decider
+            .handle_event(&self.state, event, map, occupancy)
+        {
+            self.action = action;
+            self.progress = 0.0;
+            self.subunit_position = (self.state.position.x as f64, self.state.position.y as f64);
+        }
+    }
+
+    /// Write an `agent` record followed by the records of its decider.
+    pub fn save(&mut self, kind: AgentKind, writer: &mut SaveWriter) {
+        let mut fields = vec![
+            "agent".to_string(),
+            kind.name().to_string(),
+            self.state.id.to_string(),
+            self.state.position.x.to_string(),
+            self.state.position.y.to_string(),
+            self.subunit_position.0.to_string(),
+            self.subunit_position.1.to_string(),
+        ];
+        match self.action {
+            AgentAction::Dead => fields.push("dead".to_string()),
+            AgentAction::Idle => fields.push("idle".to_string()),
+            AgentAction::Move(direction) => {
+                fields.push("move".to_string());
+                fields.push(direction.name().to_string());
+            }
+            // Only ever held while `Agents::decide` is running.
+            AgentAction::Yield(_, _) | AgentAction::Jump(_, _) => unreachable!(),
+        }
+        writer.record(&fields);
+        self.decider.save(writer);
+    }
+
+    pub fn load(reader: &mut SaveReader) -> Result<(AgentKind, Agent), SaveError> {
+        let (line, fields) = reader.expect("agent")?;
+        let kind = AgentKind::from_name(fields.get(0).cloned().unwrap_or(""))
+            .ok_or_else(|| reader.bad_record(line))?;
+        let id = Uuid::parse_str(fields.get(1).cloned().unwrap_or(""))
+            .map_err(|_| reader.bad_record(line))?;
+        let position = Coord2 {
+            x: reader.parse(line, &fields, 2)?,
+            y: reader.parse(line, &fields, 3)?,
+        };
+        let subunit_position: (f64, f64) = (
+            reader.parse(line, &fields, 4)?,
+            reader.parse(line, &fields, 5)?,
+        );
+        let action = match fields.get(6).cloned() {
+            Some("dead") => AgentAction::Dead,
+            Some("idle") => AgentAction::Idle,
+            Some("move") => AgentAction::Move(
+                Direction::from_name(fields.get(7).cloned().unwrap_or(""))
+                    .ok_or_else(|| reader.bad_record(line))?,
+            ),
+            _ => return Err(reader.bad_record(line)),
+        };
+        let decider = reader.decider()?;
+        // Progress through a move is however far the agent has been drawn from its tile.
+        let progress = f64::max(
+            (subunit_position.0 - position.x as f64).abs(),
+            (subunit_position.1 - position.y as f64).abs(),
+        );
+        let agent = Agent {
+            state: AgentState { id, position },
+            action,
+            subunit_position,
+            progress,
+            ready: false,
+            decider,
+        };
+        Ok((kind, agent))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -167,9 +513,33 @@ pub trait Decider: Debug {
         &mut self,
         agent: &AgentState,
         map: &Map,
+        occupancy: &Occupancy,
         rng: &mut Box<RngCore>,
     ) -> AgentAction;
 
+    /// The origins of the buildings this agent holds a place in, for `Occupancy`.
+    fn places(&self, agent: &AgentState) -> Vec<Coord2>;
+
+    /// The tag under which this kind of decider is registered in a `DeciderRegistry`.
+    fn tag(&self) -> &'static str;
+
+    /// Write a `decider` record beginning with `self.tag()`, plus any further records
+    /// needed to restore this decider's state with its registered `DeciderLoader`.
+    fn save(&mut self, writer: &mut SaveWriter);
+
+    /// Move every location this decider holds by `offset`, as the map is resized.
+    fn shift(&mut self, offset: (i64, i64));
+
+    /// React to a change to the map, returning an action to replace the current
+    /// one if it can no longer be carried out.
+    fn handle_event(
+        &mut self,
+        agent: &AgentState,
+        event: &MapEvent,
+        map: &Map,
+        occupancy: &Occupancy,
+    ) -> Option<AgentAction>;
+
     // @TODO: Figure out how to implement respawning. This would require an agent to know
     // where it should respawn, which imposes some tough information requirements.
     //fn respawn(&mut self, agent: &AgentState, map: &Map, rng: &mut Box<Rng>) -> bool;
@@ -188,22 +558,122 @@ pub enum ResidentState {
     Working,
 }
 
+impl ResidentState {
+    fn shifted(&self, offset: (i64, i64)) -> ResidentState {
+        match *self {
+            ResidentState::GoingToShop(pos) => ResidentState::GoingToShop(pos.shifted(offset)),
+            ResidentState::GoingToDrink(pos) => ResidentState::GoingToDrink(pos.shifted(offset)),
+            state => state,
+        }
+    }
+
+    fn save(&self) -> Vec<String> {
+        let (name, target) = match *self {
+            ResidentState::MovingIn => ("moving-in", None),
+            ResidentState::GoingHome => ("going-home", None),
+            ResidentState::AtHome => ("at-home", None),
+            ResidentState::GoingToShop(pos) => ("going-to-shop", Some(pos)),
+            ResidentState::Shopping => ("shopping", None),
+            ResidentState::GoingToDrink(pos) => ("going-to-drink", Some(pos)),
+            ResidentState::Drinking => ("drinking", None),
+            ResidentState::GoingToWork => ("going-to-work", None),
+            ResidentState::Working => ("working", None),
+        };
+        let mut fields = vec![name.to_string()];
+        if let Some(target) = target {
+            fields.push(target.x.to_string());
+            fields.push(target.y.to_string());
+        }
+        fields
+    }
+
+    fn load(fields: &[&str]) -> Option<ResidentState> {
+        let target = || {
+            Some(Coord2 {
+                x: fields.get(1)?.parse().ok()?,
+                y: fields.get(2)?.parse().ok()?,
+            })
+        };
+        Some(match *fields.first()? {
+            "moving-in" => ResidentState::MovingIn,
+            "going-home" => ResidentState::GoingHome,
+            "at-home" => ResidentState::AtHome,
+            "going-to-shop" => ResidentState::GoingToShop(target()?),
+            "shopping" => ResidentState::Shopping,
+            "going-to-drink" => ResidentState::GoingToDrink(target()?),
+            "drinking" => ResidentState::Drinking,
+            "going-to-work" => ResidentState::GoingToWork,
+            "working" => ResidentState::Working,
+            _ => return None,
+        })
+    }
+}
+
+/// Below this satisfaction, residents may move out of town.
+pub const UNHAPPY_SATISFACTION: f64 = 0.15;
+
 #[derive(Clone, Debug)]
 pub struct ResidentDecider {
     home: Coord2,
     work: Option<Coord2>,
     state: ResidentState,
+    route: RouteCache,
 }
 
 impl ResidentDecider {
+    pub const TAG: &'static str = "resident";
+
     pub fn new(home: Coord2) -> ResidentDecider {
         ResidentDecider {
             home: home,
             work: None,
             state: ResidentState::MovingIn,
+            route: RouteCache::new(),
         }
     }
 
+    /// A new resident for every place in the house at `home`, setting off from `from`.
+    pub fn household(map: &Map, home: Coord2, from: Coord2) -> Vec<Agent> {
+        let places = map.capacity(home).unwrap_or(1);
+        (0..places)
+            .map(|_| Agent::new(from, Box::new(ResidentDecider::new(home))))
+            .collect()
+    }
+
+    /// Loads the fields `home-x home-y (work-x work-y | none) state...`.
+    pub fn load(
+        line: usize,
+        fields: &[&str],
+        reader: &mut SaveReader,
+    ) -> Result<Box<Decider + Send + Sync>, SaveError> {
+        match Self::load_fields(fields) {
+            Some(decider) => Ok(Box::new(decider)),
+            None => Err(reader.bad_record(line)),
+        }
+    }
+
+    fn load_fields(fields: &[&str]) -> Option<ResidentDecider> {
+        let home = Coord2 {
+            x: fields.get(0)?.parse().ok()?,
+            y: fields.get(1)?.parse().ok()?,
+        };
+        let (work, state_fields) = if *fields.get(2)? == "none" {
+            (None, &fields[3..])
+        } else {
+            let work = Coord2 {
+                x: fields.get(2)?.parse().ok()?,
+                y: fields.get(3)?.parse().ok()?,
+            };
+            (Some(work), &fields[4..])
+        };
+        Some(ResidentDecider {
+            home,
+            work,
+            state: ResidentState::load(state_fields)?,
+            route: RouteCache::new(),
+        })
+    }
+
     fn going_to(
         &mut self,
         agent: &AgentState,
@@ -211,7 +681,7 @@ impl ResidentDecider {
         to: Coord2,
         and_then: (ResidentState, AgentAction),
     ) -> AgentAction {
-        match direction_of_route(map, agent.position, to) {
+        match self.route.direction(map, agent.position, to) {
             RouteDirection::NotRouteable => AgentAction::Dead,
             RouteDirection::Complete => {
                 self.state = and_then.0;
@@ -221,11 +691,47 @@ impl ResidentDecider {
         }
     }
 
+    /// How happy this resident is with where they live, from 0 to 1.
+    pub fn satisfaction(&self, map: &Map) -> f64 {
+        map.land_value(self.home)
+    }
+
     fn go_home(&mut self, agent: &AgentState, map: &Map) -> AgentAction {
         let home = self.home;
         self.state = ResidentState::GoingHome;
         self.going_to(agent, map, home, (ResidentState::AtHome, AgentAction::Idle))
     }
+
+    /// Set off for the closest saloon or general store with room, if any.
+    fn visit(
+        &mut self,
+        agent: &AgentState,
+        map: &Map,
+        occupancy: &Occupancy,
+        building: Building,
+    ) -> Option<AgentAction> {
+        let venue = occupancy.closest_with_room(map, agent.position, building)?;
+        let (going, done) = match building {
+            Building::Saloon => (ResidentState::GoingToDrink(venue), ResidentState::Drinking),
+            _ => (ResidentState::GoingToShop(venue), ResidentState::Shopping),
+        };
+        self.state = going;
+        Some(self.going_to(agent, map, venue, (done, AgentAction::Idle)))
+    }
+
+    /// Having found a venue full on arrival, try the next closest, or go home.
+    fn visit_elsewhere(
+        &mut self,
+        agent: &AgentState,
+        map: &Map,
+        occupancy: &Occupancy,
+        building: Building,
+    ) -> AgentAction {
+        match self.visit(agent, map, occupancy, building) {
+            Some(action) => action,
+            None => self.go_home(agent, map),
+        }
+    }
 }
 
 impl Decider for ResidentDecider {
@@ -233,19 +739,18 @@ impl Decider for ResidentDecider {
         &mut self,
         agent: &AgentState,
         map: &Map,
+        occupancy: &Occupancy,
         rng: &mut Box<RngCore>,
     ) -> AgentAction {
-        // Ensure that idle residents are killed if the square they are on is deleted.
-        //if map.get(agent.position).is_none() {
-        //    return AgentAction::Dead;
-        //}
-
         let home = self.home;
         match self.state {
             ResidentState::MovingIn | ResidentState::GoingHome => {
                 self.going_to(agent, map, home, (ResidentState::AtHome, AgentAction::Idle))
             }
             ResidentState::AtHome => {
+                if self.satisfaction(map) < UNHAPPY_SATISFACTION && rng.gen_bool(1.0 / 500.0) {
+                    return AgentAction::Dead;
+                }
                 let go_drinking = rng.gen_bool(1.0 / 60.0);
                 let go_shopping = rng.gen_bool(1.0 / 40.0);
                 let go_working = rng.gen_bool(1.0 / 80.0);
@@ -256,34 +761,28 @@ impl Decider for ResidentDecider {
                     let work = self.work.unwrap();
                     self.going_to(agent, map, work, and_then)
                 } else {
-                    let (building_type, state_constructor, done_state): (
-                        Building,
-                        fn(Coord2) -> ResidentState,
-                        ResidentState,
-                    ) = if go_drinking {
-                        (
-                            Building::Saloon,
-                            ResidentState::GoingToDrink,
-                            ResidentState::Drinking,
-                        )
+                    let building = if go_drinking {
+                        Building::Saloon
                     } else if go_shopping {
-                        (
-                            Building::GeneralStore,
-                            ResidentState::GoingToShop,
-                            ResidentState::Shopping,
-                        )
+                        Building::GeneralStore
                     } else {
                         return AgentAction::Idle;
                     };
-                    let closest = match closest_building(map, agent.position, building_type) {
-                        Some(closest) => closest,
-                        None => return AgentAction::Dead,
-                    };
-                    self.state = state_constructor(closest);
-                    self.going_to(agent, map, closest, (done_state, AgentAction::Idle))
+                    let all_full = map.buildings.get(&building).map_or(false, |venues| {
+                        !venues.is_empty() && occupancy.with_room(map, building).is_empty()
+                    });
+                    match self.visit(agent, map, occupancy, building) {
+                        Some(action) => action,
+                        // Wait for a place rather than leave town.
+                        None if all_full => AgentAction::Idle,
+                        None => AgentAction::Dead,
+                    }
                 }
             }
             ResidentState::GoingToShop(shop_pos) => {
+                if agent.position == shop_pos && !occupancy.has_room(map, shop_pos) {
+                    return self.visit_elsewhere(agent, map, occupancy, Building::GeneralStore);
+                }
                 let and_then = (ResidentState::Shopping, AgentAction::Idle);
                 self.going_to(agent, map, shop_pos, and_then)
             }
@@ -295,14 +794,22 @@ impl Decider for ResidentDecider {
                 }
             }
             ResidentState::GoingToDrink(saloon_pos) => {
+                if agent.position == saloon_pos && !occupancy.has_room(map, saloon_pos) {
+                    return self.visit_elsewhere(agent, map, occupancy, Building::Saloon);
+                }
                 let and_then = (ResidentState::Drinking, AgentAction::Idle);
                 self.going_to(agent, map, saloon_pos, and_then)
             }
             ResidentState::Drinking => {
                 let chance_of_new_job = if self.work.is_none() { 0.01 } else { 0.001 };
-                if rng.gen_bool(chance_of_new_job) {
-                    let random_factory = *rng.choose(&map.buildings[&Building::Factory]).unwrap();
-                    self.work = Some(random_factory);
+                let new_job = if rng.gen_bool(chance_of_new_job) {
+                    rng.choose(&occupancy.with_room(map, Building::Factory))
+                        .cloned()
+                } else {
+                    None
+                };
+                if let Some(factory) = new_job {
+                    self.work = Some(factory);
                     self.state = ResidentState::GoingToWork;
                     let and_then = (ResidentState::Working, AgentAction::Idle);
                     let work = self.work.unwrap();
@@ -315,6 +822,10 @@ impl Decider for ResidentDecider {
             }
             ResidentState::GoingToWork => {
                 let work = self.work.unwrap();
+                // Come back another day if every place there is taken for now.
+                if agent.position == work && !occupancy.has_room(map, work) {
+                    return self.go_home(agent, map);
+                }
                 let and_then = (ResidentState::Working, AgentAction::Idle);
                 self.going_to(agent, map, work, and_then)
             }
@@ -327,6 +838,103 @@ impl Decider for ResidentDecider {
             }
         }
     }
+
+    fn tag(&self) -> &'static str {
+        Self::TAG
+    }
+
+    fn save(&mut self, writer: &mut SaveWriter) {
+        let mut fields = vec![
+            "decider".to_string(),
+            Self::TAG.to_string(),
+            self.home.x.to_string(),
+            self.home.y.to_string(),
+        ];
+        match self.work {
+            Some(work) => {
+                fields.push(work.x.to_string());
+                fields.push(work.y.to_string());
+            }
+            None => fields.push("none".to_string()),
+        }
+        fields.extend(self.state.save());
+        writer.record(&fields);
+    }
+
+    fn shift(&mut self, offset: (i64, i64)) {
+        self.home = self.home.shifted(offset);
+        self.work = self.work.map(|work| work.shifted(offset));
+        self.state = self.state.shifted(offset);
+        self.route.clear();
+    }
+
+    fn places(&self, agent: &AgentState) -> Vec<Coord2> {
+        let mut places = vec![self.home];
+        match self.state {
+            ResidentState::Working => places.extend(self.work),
+            // Whoever is served stands at the origin of the building serving them.
+            ResidentState::Shopping | ResidentState::Drinking => places.push(agent.position),
+            _ => {}
+        }
+        places
+    }
+
+    fn handle_event(
+        &mut self,
+        agent: &AgentState,
+        event: &MapEvent,
+        map: &Map,
+        occupancy: &Occupancy,
+    ) -> Option<AgentAction> {
+        self.route.handle(event);
+        match *event {
+            // Stranded on water where a bridge was, with nowhere to walk.
+            MapEvent::PavingRemoved(location) if location == agent.position => {
+                if !map.can_walk(location) {
+                    return Some(AgentAction::Dead);
+                }
+            }
+            MapEvent::BuildingDeleted(origin, _) if origin == self.home => {
+                // Move into the closest other house with room, or leave town.
+                match occupancy.closest_with_room(map, agent.position, Building::House) {
+                    Some(house) => self.home = house,
+                    None => return Some(AgentAction::Dead),
+                }
+            }
+            MapEvent::BuildingDeleted(origin, _) if Some(origin) == self.work => {
+                self.work = None;
+                if let ResidentState::GoingToWork | ResidentState::Working = self.state {
+                    self.state = ResidentState::GoingHome;
+                }
+            }
+            MapEvent::BuildingDeleted(origin, building) => {
+                // Head for the closest other building of the same kind instead, or home.
+                let closest = || occupancy.closest_with_room(map, agent.position, building);
+                self.state = match self.state {
+                    ResidentState::GoingToShop(target) if target == origin => {
+                        closest().map_or(ResidentState::GoingHome, ResidentState::GoingToShop)
+                    }
+                    ResidentState::GoingToDrink(target) if target == origin => {
+                        closest().map_or(ResidentState::GoingHome, ResidentState::GoingToDrink)
+                    }
+                    state => state,
+                };
+            }
+            _ => {}
+        }
+        // Whoever was inside a building which is gone heads home.
+        let inside = match self.state {
+            ResidentState::AtHome
+            | ResidentState::Shopping
+            | ResidentState::Drinking
+            | ResidentState::Working => true,
+            _ => false,
+        };
+        if inside && map.get(agent.position).is_none() {
+            self.state = ResidentState::GoingHome;
+        }
+        None
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -338,28 +946,148 @@ pub enum TrainState {
 
 #[derive(Debug)]
 pub struct TrainDecider {
-    platform: Coord2,
+    /// The station most recently stopped at or passed through, so that the train
+    /// does not stop there again as it pulls away.
+    stopped_at: Option<Coord2>,
     state: TrainState,
     passenger_rx: Mutex<mpsc::Receiver<Agent>>,
     pub passengers: Vec<Agent>,
+    /// Passengers taken off the channel to be saved or shifted, but not yet on
+    /// board. They board ahead of any still in the channel.
+    waiting: Vec<Agent>,
 }
 
+impl TrainState {
+    fn save(&self) -> Vec<String> {
+        match *self {
+            TrainState::Arriving => vec!["arriving".to_string()],
+            TrainState::AtPlatform(remaining) => {
+                vec!["at-platform".to_string(), remaining.to_string()]
+            }
+            TrainState::Departing => vec!["departing".to_string()],
+        }
+    }
+
+    fn load(fields: &[&str]) -> Option<TrainState> {
+        Some(match *fields.first()? {
+            "arriving" => TrainState::Arriving,
+            "at-platform" => TrainState::AtPlatform(fields.get(1)?.parse().ok()?),
+            "departing" => TrainState::Departing,
+            _ => return None,
+        })
+    }
+}
+
+/// How far past the edge of the map a train runs before it is ready to start
+/// over, so that all of its carriages leave the screen.
+const TRAIN_OVERRUN: u64 = 20;
+
 impl TrainDecider {
-    pub fn new(platform: Coord2, passenger_rx: mpsc::Receiver<Agent>) -> TrainDecider {
+    pub const TAG: &'static str = "train";
+
+    pub fn new(passenger_rx: mpsc::Receiver<Agent>) -> TrainDecider {
         let mut d = TrainDecider {
-            platform: platform,
+            stopped_at: None,
             state: TrainState::Arriving,
             passenger_rx: Mutex::new(passenger_rx),
             passengers: Vec::new(),
+            waiting: Vec::new(),
         };
         d.take_all_ready_passengers();
         d
     }
 
+    /// Loads the fields `passenger-count waiting-count (stopped-x stopped-y | none)
+    /// state...`, then reads an agent for every passenger on board and every one
+    /// still waiting to board.
+    pub fn load(
+        line: usize,
+        fields: &[&str],
+        reader: &mut SaveReader,
+    ) -> Result<Box<Decider + Send + Sync>, SaveError> {
+        let passenger_count: usize = reader.parse(line, fields, 0)?;
+        let waiting_count: usize = reader.parse(line, fields, 1)?;
+        let (stopped_at, state_fields) = if fields.get(2) == Some(&"none") {
+            (None, &fields[3..])
+        } else {
+            let stopped_at = Coord2 {
+                x: reader.parse(line, fields, 2)?,
+                y: reader.parse(line, fields, 3)?,
+            };
+            (Some(stopped_at), &fields[4..])
+        };
+        let state = TrainState::load(state_fields).ok_or_else(|| reader.bad_record(line))?;
+        let mut passengers = Vec::with_capacity(passenger_count);
+        for _ in 0..passenger_count {
+            passengers.push(Agent::load(reader)?.1);
+        }
+        let mut waiting = Vec::with_capacity(waiting_count);
+        for _ in 0..waiting_count {
+            waiting.push(Agent::load(reader)?.1);
+        }
+        Ok(Box::new(TrainDecider {
+            stopped_at,
+            state,
+            passenger_rx: Mutex::new(reader.take_passenger_rx()),
+            passengers,
+            waiting,
+        }))
+    }
+
     fn take_all_ready_passengers(&mut self) {
+        self.take_waiting_passengers();
+        self.passengers.append(&mut self.waiting);
+    }
+
+    /// Take passengers off the channel without boarding them, so that saving or
+    /// shifting the train leaves when they board unchanged.
+    fn take_waiting_passengers(&mut self) {
         let passenger_rx = self.passenger_rx.lock().unwrap();
         while let Ok(passenger) = passenger_rx.try_recv() {
-            self.passengers.push(passenger);
+            self.waiting.push(passenger);
+        }
+    }
+
+    /// Once off the end of the line, wait for passengers and then start over, on
+    /// the next line down with a station so that every station on a line from the
+    /// west edge is served in turn.
+    fn start_over(&mut self, agent: &AgentState, map: &Map, rng: &mut Box<RngCore>) -> AgentAction {
+        let mut rows: Vec<u64> = map.buildings.get(&Building::TrainStation).map_or(vec![], |origins| {
+            origins
+                .iter()
+                .filter(|origin| map.on_the_line(**origin))
+                .map(|origin| origin.y)
+                .collect()
+        });
+        rows.sort();
+        rows.dedup();
+        let row = agent.position.y;
+        let line_start = Coord2 {
+            x: 0,
+            y: rows
+                .iter()
+                .cloned()
+                .find(|&station_row| station_row > row)
+                .or_else(|| rows.first().cloned())
+                .unwrap_or(row),
+        };
+        // When we have some passengers, we'd like to delay 1 update so that
+        // it doesn't look weirdly immediate that the train appears.
+        // When we don't have passengers, set off each 1-in-68 updates.
+        if !self.passengers.is_empty() {
+            self.take_all_ready_passengers();
+            self.state = TrainState::Arriving;
+            self.stopped_at = None;
+            AgentAction::Jump(line_start, Box::new(AgentAction::Move(Direction::East)))
+        } else {
+            self.take_all_ready_passengers();
+            if rng.gen_bool(1.0 / 68.0) {
+                self.state = TrainState::Arriving;
+                self.stopped_at = None;
+                AgentAction::Jump(line_start, Box::new(AgentAction::Move(Direction::East)))
+            } else {
+                AgentAction::Idle
+            }
         }
     }
 }
@@ -368,16 +1096,26 @@ impl Decider for TrainDecider {
     fn decide_action(
         &mut self,
         agent: &AgentState,
-        _: &Map,
+        map: &Map,
+        _occupancy: &Occupancy,
         rng: &mut Box<RngCore>,
     ) -> AgentAction {
+        let line_end = map.width() + TRAIN_OVERRUN;
         match self.state {
             TrainState::Arriving => {
                 let head_pos = Coord2 {
                     x: agent.position.x.saturating_sub(1),
                     y: agent.position.y,
                 };
-                if head_pos == self.platform {
+                // Stations span several tiles, so are told apart by their origin.
+                let station = map
+                    .get(head_pos)
+                    .and_then(Tile::as_rails)
+                    .and_then(|rails_tile| rails_tile.station.as_ref())
+                    .map(|station| station.origin)
+                    .filter(|_| map.on_the_line(head_pos));
+                if station.is_some() && self.stopped_at != station {
+                    self.stopped_at = station;
                     // In general we don't want to take new passengers during a ride. But.
                     // If a train is about to pass straight through with no passengers and
                     // yet the player has placed some houses, it'd be nice to not waste time
@@ -387,7 +1125,6 @@ impl Decider for TrainDecider {
                     }
                     if self.passengers.is_empty() {
                         // If no passengers, skip stopping at the platform.
-                        self.state = TrainState::Departing;
                         AgentAction::Move(Direction::East)
                     } else {
                         // Shuffle passengers to prevent noticable patterns.
@@ -397,6 +1134,9 @@ impl Decider for TrainDecider {
                         self.state = TrainState::AtPlatform(18);
                         AgentAction::Idle
                     }
+                } else if agent.position.x >= line_end {
+                    self.state = TrainState::Departing;
+                    self.start_over(agent, map, rng)
                 } else {
                     AgentAction::Move(Direction::East)
                 }
@@ -404,44 +1144,299 @@ impl Decider for TrainDecider {
             TrainState::AtPlatform(remaining) => {
                 if remaining > 0 || !self.passengers.is_empty() {
                     self.state = TrainState::AtPlatform(remaining.saturating_sub(1));
-                    if let Some(passenger) = self.passengers.pop() {
+                    if let Some(mut passenger) = self.passengers.pop() {
+                        // Passengers step off at the station, wherever they boarded.
+                        let platform = self.stopped_at.unwrap_or(agent.position);
+                        passenger.state.position = platform;
+                        passenger.subunit_position = (platform.x as f64, platform.y as f64);
                         AgentAction::Yield(vec![passenger], Box::new(AgentAction::Idle))
                     } else {
                         AgentAction::Idle
                     }
                 } else {
-                    self.state = TrainState::Departing;
+                    // Carry on to any further stations down the line.
+                    self.state = TrainState::Arriving;
                     AgentAction::Move(Direction::East)
                 }
             }
             TrainState::Departing => {
-                if agent.position.x < 100 {
+                if agent.position.x < line_end {
                     AgentAction::Move(Direction::East)
                 } else {
-                    // When we have some passengers, we'd like to delay 1 update so that
-                    // it doesn't look weirdly immediate that the train appears.
-                    // When we don't have passengers, set off each 1-in-68 updates.
-                    if !self.passengers.is_empty() {
-                        self.take_all_ready_passengers();
-                        self.state = TrainState::Arriving;
-                        AgentAction::Jump(
-                            Coord2 { x: 0, y: 0 },
-                            Box::new(AgentAction::Move(Direction::East)),
-                        )
-                    } else {
-                        self.take_all_ready_passengers();
-                        if rng.gen_bool(1.0 / 68.0) {
-                            self.state = TrainState::Arriving;
-                            AgentAction::Jump(
-                                Coord2 { x: 0, y: 0 },
-                                Box::new(AgentAction::Move(Direction::East)),
-                            )
-                        } else {
-                            AgentAction::Idle
-                        }
+                    self.start_over(agent, map, rng)
+                }
+            }
+        }
+    }
+
+    fn tag(&self) -> &'static str {
+        Self::TAG
+    }
+
+    fn save(&mut self, writer: &mut SaveWriter) {
+        // Passengers still waiting in the channel would otherwise be lost.
+        self.take_waiting_passengers();
+        let mut fields = vec![
+            "decider".to_string(),
+            Self::TAG.to_string(),
+            self.passengers.len().to_string(),
+            self.waiting.len().to_string(),
+        ];
+        match self.stopped_at {
+            Some(stopped_at) => {
+                fields.push(stopped_at.x.to_string());
+                fields.push(stopped_at.y.to_string());
+            }
+            None => fields.push("none".to_string()),
+        }
+        fields.extend(self.state.save());
+        writer.record(&fields);
+        for passenger in self.passengers.iter_mut().chain(&mut self.waiting) {
+            passenger.save(AgentKind::Resident, writer);
+        }
+    }
+
+    fn shift(&mut self, offset: (i64, i64)) {
+        // Passengers still waiting in the channel are headed for the old locations too.
+        self.take_waiting_passengers();
+        self.stopped_at = self.stopped_at.map(|stopped_at| stopped_at.shifted(offset));
+        for passenger in self.passengers.iter_mut().chain(&mut self.waiting) {
+            passenger.shift(offset);
+        }
+    }
+
+    fn places(&self, _agent: &AgentState) -> Vec<Coord2> {
+        // Passengers on board already hold places in their homes.
+        self.passengers.iter().flat_map(Agent::places).collect()
+    }
+
+    fn handle_event(
+        &mut self,
+        _agent: &AgentState,
+        event: &MapEvent,
+        map: &Map,
+        occupancy: &Occupancy,
+    ) -> Option<AgentAction> {
+        // Passengers are not on the map yet, so only the buildings they are bound for
+        // matter to them.
+        if let MapEvent::BuildingDeleted(_, _) = *event {
+            self.take_waiting_passengers();
+            for passengers in vec![&mut self.passengers, &mut self.waiting] {
+                for mut passenger in mem::replace(passengers, vec![]) {
+                    passenger.handle_event(event, map, occupancy);
+                    if let AgentAction::Dead = passenger.action {
+                        continue;
                     }
+                    passengers.push(passenger);
                 }
             }
         }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn residents_move_house_or_leave_when_theirs_is_deleted() {
+        let (old, new) = (Coord2 { x: 12, y: 4 }, Coord2 { x: 12, y: 8 });
+        let mut map = map_with_street(&[(old, Building::House), (new, Building::House)]);
+        let agent = AgentState {
+            id: Uuid::new_v4(),
+            position: old,
+        };
+        let mut decider = ResidentDecider::new(old);
+        decider.state = ResidentState::AtHome;
+
+        map.delete(old).unwrap();
+        let event = MapEvent::BuildingDeleted(old, Building::House);
+        let occupancy = Occupancy::default();
+        assert!(decider
+            .handle_event(&agent, &event, &map, &occupancy)
+            .is_none());
+        assert_eq!(decider.home, new);
+        assert_eq!(decider.state, ResidentState::GoingHome);
+
+        map.delete(new).unwrap();
+        let event = MapEvent::BuildingDeleted(new, Building::House);
+        match decider.handle_event(&agent, &event, &map, &occupancy) {
+            Some(AgentAction::Dead) => {}
+            other => panic!("expected the resident to leave, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn residents_try_the_next_closest_venue_when_one_is_full() {
+        let mut map = Map::new(Coord2 { x: 30, y: 30 });
+        for y in 3..20 {
+            map.pave(Coord2 { x: 15, y }).unwrap();
+        }
+        let (full, other) = (Coord2 { x: 17, y: 4 }, Coord2 { x: 17, y: 12 });
+        map.build(full, Building::GeneralStore).unwrap();
+        map.build(other, Building::GeneralStore).unwrap();
+        let mut occupancy = Occupancy::default();
+        occupancy.take(&vec![full; CUSTOMERS_PER_SHOP as usize]);
+        assert!(!occupancy.has_room(&map, full));
+        assert_eq!(
+            occupancy.with_room(&map, Building::GeneralStore),
+            vec![other]
+        );
+
+        let agent = AgentState {
+            id: Uuid::new_v4(),
+            position: full,
+        };
+        let mut decider = ResidentDecider::new(Coord2 { x: 0, y: 0 });
+        decider.state = ResidentState::GoingToShop(full);
+        let mut rng: Box<RngCore> = Box::new(thread_rng());
+        decider.decide_action(&agent, &map, &occupancy, &mut rng);
+        assert_eq!(decider.state, ResidentState::GoingToShop(other));
+    }
+
+    #[test]
+    fn full_venues_nearby_are_passed_over_for_the_next_closest() {
+        let mut map = Map::new(Coord2 { x: 40, y: 60 });
+        for y in 3..58 {
+            map.pave(Coord2 { x: 20, y }).unwrap();
+        }
+        let stores: Vec<Coord2> = (0..5)
+            .map(|i| Coord2 {
+                x: 22,
+                y: 4 + 10 * i,
+            })
+            .collect();
+        for &store in &stores {
+            map.build(store, Building::GeneralStore).unwrap();
+        }
+        let from = Coord2 { x: 20, y: 3 };
+        let mut occupancy = Occupancy::default();
+        assert_eq!(
+            occupancy.closest_with_room(&map, from, Building::GeneralStore),
+            Some(stores[0])
+        );
+
+        occupancy.take(&vec![stores[0]; CUSTOMERS_PER_SHOP as usize]);
+        assert_eq!(
+            occupancy.closest_with_room(&map, from, Building::GeneralStore),
+            Some(stores[1])
+        );
+
+        // Every store among the nearest is full, so it must look further out.
+        for &store in &stores[1..4] {
+            occupancy.take(&vec![store; CUSTOMERS_PER_SHOP as usize]);
+        }
+        assert_eq!(
+            occupancy.closest_with_room(&map, from, Building::GeneralStore),
+            Some(stores[4])
+        );
+        occupancy.take(&vec![stores[4]; CUSTOMERS_PER_SHOP as usize]);
+        assert_eq!(
+            occupancy.closest_with_room(&map, from, Building::GeneralStore),
+            None
+        );
+    }
+
+    #[test]
+    fn residents_hold_a_job_only_while_at_work() {
+        let (home, factory, saloon) = (
+            Coord2 { x: 2, y: 2 },
+            Coord2 { x: 8, y: 2 },
+            Coord2 { x: 2, y: 8 },
+        );
+        let mut decider = ResidentDecider::new(home);
+        decider.work = Some(factory);
+        let mut agent = AgentState {
+            id: Uuid::nil(),
+            position: saloon,
+        };
+        decider.state = ResidentState::Drinking;
+        assert_eq!(decider.places(&agent), vec![home, saloon]);
+
+        agent.position = factory;
+        decider.state = ResidentState::Working;
+        assert_eq!(decider.places(&agent), vec![home, factory]);
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_city_tick_for_tick() {
+        let run = |seed| {
+            let mut map = Map::new(Coord2 { x: 30, y: 30 });
+            for y in 3..20 {
+                map.pave(Coord2 { x: 15, y }).unwrap();
+            }
+            let home = Coord2 { x: 17, y: 4 };
+            map.build(home, Building::House).unwrap();
+            map.build(Coord2 { x: 12, y: 6 }, Building::Factory)
+                .unwrap();
+            map.build(Coord2 { x: 17, y: 8 }, Building::GeneralStore)
+                .unwrap();
+            map.build(Coord2 { x: 17, y: 12 }, Building::Saloon)
+                .unwrap();
+            let mut agents = Agents::new(4, seed);
+            for agent in ResidentDecider::household(&map, home, Coord2 { x: 15, y: 19 }) {
+                agents.insert(AgentKind::Resident, agent);
+            }
+            (0..400)
+                .map(|_| {
+                    agents.update(&map);
+                    let mut writer = SaveWriter::new();
+                    agents.save(&mut writer);
+                    writer.finish()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(run(7), run(7));
+    }
+
+    #[test]
+    fn saving_leaves_passengers_waiting_for_the_train() {
+        let mut map = Map::new(Coord2 { x: 30, y: 30 });
+        for x in 0..30 {
+            map.rail(Coord2 { x, y: 0 }).unwrap();
+        }
+        // Join the road already laid at (15, 2).
+        for x in (0..15).chain(16..30) {
+            map.pave(Coord2 { x, y: 2 }).unwrap();
+        }
+        for y in 3..10 {
+            map.pave(Coord2 { x: 15, y }).unwrap();
+        }
+        map.build(Coord2 { x: 10, y: 0 }, Building::TrainStation)
+            .unwrap();
+        let home = Coord2 { x: 17, y: 4 };
+        map.build(home, Building::House).unwrap();
+
+        let run = |save_at: Option<u64>| {
+            let (passenger_tx, passenger_rx) = mpsc::channel();
+            let mut agents = Agents::new(4, 7);
+            // Off the end of the line, about to start over.
+            let train = Coord2 {
+                x: map.width() + TRAIN_OVERRUN,
+                y: 0,
+            };
+            let decider = TrainDecider::new(passenger_rx);
+            agents.insert(AgentKind::Train, Agent::new(train, Box::new(decider)));
+            for agent in ResidentDecider::household(&map, home, Coord2 { x: 15, y: 2 }) {
+                passenger_tx.send(agent).unwrap();
+            }
+            for tick in 0..200 {
+                if save_at == Some(tick) {
+                    let mut writer = SaveWriter::new();
+                    agents.save(&mut writer);
+                    let text = writer.finish();
+                    let registry = DeciderRegistry::default();
+                    let mut reader = SaveReader::new(&text, &registry, mpsc::channel().1);
+                    agents = Agents::load(&mut reader).unwrap();
+                }
+                agents.update(&map);
+            }
+            let mut writer = SaveWriter::new();
+            agents.save(&mut writer);
+            writer.finish()
+        };
+        assert_eq!(run(Some(0)), run(None));
+        assert_eq!(run(Some(50)), run(None));
     }
 }
@@ -0,0 +1,463 @@
+use super::*;
+use rand::prng::XorShiftRng;
+use rand::Rng;
+use std::sync::mpsc;
+
+const TOWN_STREAM: u64 = 4;
+/// The smallest town: room for the station halfway along the railway, with its
+/// entrance and the road below it.
+pub const MIN_TOWN_DIMENSIONS: Coord2 = Coord2 { x: 8, y: 3 };
+
+/// How the streets of a generated town are laid out.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StreetLayout {
+    /// Straight streets crossing each other a block apart.
+    Grid,
+    /// Streets which wander off, branch and sometimes meet again.
+    Organic,
+}
+
+impl StreetLayout {
+    pub fn all() -> [StreetLayout; 2] {
+        [StreetLayout::Grid, StreetLayout::Organic]
+    }
+
+    pub fn from_name(name: &str) -> Option<StreetLayout> {
+        StreetLayout::all()
+            .iter()
+            .cloned()
+            .find(|l| l.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            StreetLayout::Grid => "grid",
+            StreetLayout::Organic => "organic",
+        }
+    }
+}
+
+impl fmt::Display for StreetLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// What a town is generated from. The same settings always generate the same town.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TownSettings {
+    pub seed: u64,
+    pub dimensions: Coord2,
+    pub layout: StreetLayout,
+    /// Tiles from one street to the next one alongside it.
+    pub block_size: u64,
+    /// Chance, from 0 to 1, of building on each site near a road.
+    pub density: f64,
+}
+
+impl Default for TownSettings {
+    fn default() -> TownSettings {
+        TownSettings {
+            seed: 0,
+            dimensions: Coord2 { x: 80, y: 80 },
+            layout: StreetLayout::Grid,
+            block_size: 7,
+            density: 0.2,
+        }
+    }
+}
+
+impl TownSettings {
+    /// Generate a town, with a train in `agents` bringing a household for each house.
+    pub fn generate(
+        &self,
+        mut agents: Agents,
+        passenger_tx: &mpsc::Sender<Agent>,
+        passenger_rx: mpsc::Receiver<Agent>,
+    ) -> Result<(Map, Agents), MapError> {
+        let map = self.generate_map()?;
+        let arrival = station_road(&map).unwrap();
+        for &house in map.buildings.get(&Building::House).unwrap_or(&vec![]) {
+            for agent in ResidentDecider::household(&map, house, arrival) {
+                passenger_tx.send(agent).unwrap();
+            }
+        }
+        let train_decider = TrainDecider::new(passenger_rx);
+        let train_agent = Agent::new(Coord2 { x: 0, y: 0 }, Box::new(train_decider));
+        agents.insert(AgentKind::Train, train_agent);
+        Ok((map, agents))
+    }
+
+    /// Generate the map of a town: its ground, railway and station, streets and
+    /// buildings, with the land left over by the roads zoned to grow into.
+    pub fn generate_map(&self) -> Result<Map, MapError> {
+        if self.dimensions.x < MIN_TOWN_DIMENSIONS.x || self.dimensions.y < MIN_TOWN_DIMENSIONS.y {
+            return Err(MapError::TooSmall(self.dimensions));
+        }
+        let mut rng = self.rng();
+        let mut map = Map::new(self.dimensions);
+        // `Map::new` starts the roads below where the station goes.
+        let high_street = Coord2 {
+            x: self.dimensions.x / 2,
+            y: 2,
+        };
+
+        self.lay_terrain(&mut map, &mut rng, high_street)?;
+        let railway = Path::between(
+            Coord2 { x: 0, y: 0 },
+            Coord2 {
+                x: self.dimensions.x - 1,
+                y: 0,
+            },
+        )
+        .unwrap();
+        map.rail_path(railway)?;
+
+        match self.layout {
+            StreetLayout::Grid => self.lay_grid_streets(&mut map, high_street)?,
+            StreetLayout::Organic => self.lay_organic_streets(&mut map, &mut rng, high_street)?,
+        }
+        // Roads cannot climb hills, so the hills go round them instead.
+        self.lay_patches(&mut map, &mut rng, Terrain::Hills)?;
+        let mut avenue = Some(high_street);
+        while let Some(location) =
+            avenue.filter(|l| map.get(*l).and_then(Tile::as_paving).is_some())
+        {
+            map.upgrade_road(location)?;
+            avenue = location.step(Direction::South);
+        }
+        map.build(
+            Coord2 {
+                x: high_street.x,
+                y: 0,
+            },
+            Building::TrainStation,
+        )?;
+
+        self.place_buildings(&mut map, &mut rng)?;
+        self.zone_leftovers(&mut map, &mut rng)?;
+        map.survey_land_value();
+        Ok(map)
+    }
+
+    fn rng(&self) -> XorShiftRng {
+        seeded_rng(&[self.seed, TOWN_STREAM])
+    }
+
+    /// A river winding south through the east of the town, and patches of forest.
+    /// Ground for the station is left clear.
+    fn lay_terrain(
+        &self,
+        map: &mut Map,
+        rng: &mut XorShiftRng,
+        high_street: Coord2,
+    ) -> Result<(), MapError> {
+        let (width, height) = (self.dimensions.x, self.dimensions.y);
+        let river_bounds = (width * 3 / 4, width.saturating_sub(3));
+        let mut river = (river_bounds.0 + river_bounds.1) / 2;
+        for y in 0..height {
+            for x in river..river + 2 {
+                set_terrain_within(map, Coord2 { x, y }, Terrain::Water)?;
+            }
+            if rng.gen_bool(0.3) {
+                river = if rng.gen() {
+                    river.saturating_add(1).min(river_bounds.1)
+                } else {
+                    river.saturating_sub(1).max(river_bounds.0)
+                };
+            }
+        }
+
+        self.lay_patches(map, rng, Terrain::Forest)?;
+
+        // The station is turned to run along the railway.
+        let platform = Building::TrainStation.footprint().flip();
+        for y in 0..high_street.y {
+            for x in high_street.x..high_street.x + platform.x {
+                set_terrain_within(map, Coord2 { x, y }, Terrain::Grass)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Patches of `terrain` over grass, below the railway and wherever whatever
+    /// already stands there can stay.
+    fn lay_patches(
+        &self,
+        map: &mut Map,
+        rng: &mut XorShiftRng,
+        terrain: Terrain,
+    ) -> Result<(), MapError> {
+        let (width, height) = (self.dimensions.x, self.dimensions.y);
+        let patches = (width * height / 2000).max(1);
+        for _ in 0..patches {
+            let size = Coord2 {
+                x: rng.gen_range(4, 12),
+                y: rng.gen_range(4, 10),
+            };
+            let origin = Coord2 {
+                x: rng.gen_range(0, width),
+                y: rng.gen_range(3, height.max(4)),
+            };
+            for y in origin.y..origin.y + size.y {
+                for x in origin.x..origin.x + size.x {
+                    let location = Coord2 { x, y };
+                    let supported = map
+                        .get(location)
+                        .map_or(true, |tile| terrain.supports(tile));
+                    if map.is_bounded(location)
+                        && map.terrain(location) == Terrain::Grass
+                        && supported
+                    {
+                        map.set_terrain(location, terrain)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Streets a block apart each way, with one of them running south from the
+    /// station.
+    fn lay_grid_streets(&self, map: &mut Map, high_street: Coord2) -> Result<(), MapError> {
+        let block = self.block_size.max(1);
+        let first = Coord2 {
+            x: 1 + (high_street.x - 1) % block,
+            y: high_street.y,
+        };
+        let mut last = first;
+        while self.within_town(last + Coord2 { x: block, y: 0 }) {
+            last.x += block;
+        }
+        while self.within_town(last + Coord2 { x: 0, y: block }) {
+            last.y += block;
+        }
+
+        let mut streets = vec![];
+        for y in (first.y..last.y + 1).step_by(block as usize) {
+            streets.push(Path::between(
+                Coord2 { x: first.x, y },
+                Coord2 { x: last.x, y },
+            ));
+        }
+        for x in (first.x..last.x + 1).step_by(block as usize) {
+            streets.push(Path::between(
+                Coord2 { x, y: first.y },
+                Coord2 { x, y: last.y },
+            ));
+        }
+        for street in streets.into_iter().flatten() {
+            map.pave_path(street)?;
+        }
+        Ok(())
+    }
+
+    /// Streets grown out from the station, each running a random length before
+    /// carrying on, turning off or both. Streets stop where they meet another, or
+    /// would run too close alongside one.
+    fn lay_organic_streets(
+        &self,
+        map: &mut Map,
+        rng: &mut XorShiftRng,
+        high_street: Coord2,
+    ) -> Result<(), MapError> {
+        let block = self.block_size.max(2);
+        let mut ends = vec![
+            (high_street, Direction::South),
+            (high_street, Direction::East),
+            (high_street, Direction::West),
+        ];
+        while !ends.is_empty() {
+            let (from, direction) = ends.swap_remove(rng.gen_range(0, ends.len()));
+            let length = rng.gen_range(block / 2 + 1, block * 2);
+            let (to, stopped) = self.street_end(map, from, direction, length);
+            if to == from {
+                continue;
+            }
+            map.pave_path(Path::between(from, to).unwrap())?;
+            if stopped {
+                continue;
+            }
+            if rng.gen_bool(0.8) {
+                ends.push((to, direction));
+            }
+            for &turn in &turns(direction) {
+                if rng.gen_bool(0.4) {
+                    ends.push((to, turn));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// How far a street from `from` can run `direction`, up to `length` tiles, and
+    /// whether it had to stop short.
+    fn street_end(
+        &self,
+        map: &Map,
+        from: Coord2,
+        direction: Direction,
+        length: u64,
+    ) -> (Coord2, bool) {
+        // Leave room between streets for buildings on both sides.
+        let clearance = (self.block_size / 2).max(3);
+        let is_paved = |l: Coord2| map.get(l).and_then(Tile::as_paving).is_some();
+        let mut end = from;
+        for _ in 0..length {
+            let next = match end.step(direction) {
+                Some(l) if self.within_town(l) && !is_paved(l) && map.can_pave(l) => l,
+                _ => return (end, true),
+            };
+            let crowded = turns(direction).iter().any(|&side| {
+                let mut beside = next;
+                (0..clearance).any(|_| match beside.step(side) {
+                    Some(l) => {
+                        beside = l;
+                        is_paved(l)
+                    }
+                    None => false,
+                })
+            });
+            if crowded {
+                return (end, true);
+            }
+            end = next;
+        }
+        (end, false)
+    }
+
+    /// Build on sites by the roads at random, choosing whatever the town most
+    /// demands that fits, or else a house.
+    fn place_buildings(&self, map: &mut Map, rng: &mut XorShiftRng) -> Result<(), MapError> {
+        let mut sites: Vec<Coord2> = self
+            .locations()
+            .filter(|l| map.get(*l).is_none() && map.nearest_road(*l).is_some())
+            .collect();
+        rng.shuffle(&mut sites);
+        for site in sites {
+            if !rng.gen_bool(self.density) {
+                continue;
+            }
+            let mut buildings: Vec<Building> = Demand::of(map)
+                .wanted()
+                .into_iter()
+                .map(|zone| Growth::building_for(map, zone))
+                .collect();
+            buildings.push(Building::House);
+            if let Some(&building) = buildings.iter().find(|b| map.can_build(site, **b)) {
+                map.build(site, building)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Zone the land left by the roads, a block at a time, for the town to grow into.
+    /// Most blocks are residential.
+    fn zone_leftovers(&self, map: &mut Map, rng: &mut XorShiftRng) -> Result<(), MapError> {
+        let block = self.block_size.max(1);
+        let blocks = Coord2 {
+            x: self.dimensions.x / block + 1,
+            y: self.dimensions.y / block + 1,
+        };
+        let choices = [
+            Zone::Residential,
+            Zone::Residential,
+            Zone::Residential,
+            Zone::Commercial,
+            Zone::Industrial,
+        ];
+        let zones: Vec<Zone> = (0..blocks.x * blocks.y)
+            .map(|_| *rng.choose(&choices).unwrap())
+            .collect();
+        for location in self.locations() {
+            if map.can_zone(location) {
+                let zone = zones[(location.y / block * blocks.x + location.x / block) as usize];
+                map.set_zone(location, Some(zone))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn locations(&self) -> Box<Iterator<Item = Coord2>> {
+        let dimensions = self.dimensions;
+        Box::new(
+            (0..dimensions.y).flat_map(move |y| (0..dimensions.x).map(move |x| Coord2 { x, y })),
+        )
+    }
+
+    /// Whether `location` is clear of the map's edges and of the railway along the top.
+    fn within_town(&self, location: Coord2) -> bool {
+        location.x >= 1
+            && location.y >= 2
+            && location.x + 1 < self.dimensions.x
+            && location.y + 1 < self.dimensions.y
+    }
+}
+
+/// The road at the entrance of the first station, where new residents step off.
+pub fn station_road(map: &Map) -> Option<Coord2> {
+    let station = *map.buildings.get(&Building::TrainStation)?.first()?;
+    let entryway = map.building_at(station)?.entryway_pos;
+    match map.get(entryway) {
+        Some(&Tile::Entrance(ref entrance)) => Some(entrance.road_pos),
+        _ => None,
+    }
+}
+
+/// The two directions at right angles to `direction`.
+fn turns(direction: Direction) -> [Direction; 2] {
+    match direction.orientation() {
+        Orientation::Vertical => [Direction::East, Direction::West],
+        Orientation::Horizontal => [Direction::North, Direction::South],
+    }
+}
+
+fn set_terrain_within(map: &mut Map, location: Coord2, terrain: Terrain) -> Result<(), MapError> {
+    if map.is_bounded(location) {
+        map.set_terrain(location, terrain)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_generates_the_same_town() {
+        for &layout in StreetLayout::all().iter() {
+            let settings = TownSettings {
+                seed: 7,
+                dimensions: Coord2 { x: 40, y: 40 },
+                layout,
+                ..TownSettings::default()
+            };
+            let map = settings.generate_map().unwrap();
+            assert!(map.validate().is_empty());
+            assert!(station_road(&map).is_some());
+            assert!(map.buildings.get(&Building::House).map_or(0, |h| h.len()) > 0);
+            assert_eq!(
+                map.to_string(),
+                settings.generate_map().unwrap().to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn towns_too_small_for_a_station_are_refused() {
+        let too_small = Coord2 { x: 7, y: 20 };
+        let settings = TownSettings {
+            dimensions: too_small,
+            ..TownSettings::default()
+        };
+        assert_eq!(
+            settings.generate_map().err(),
+            Some(MapError::TooSmall(too_small))
+        );
+        let smallest = TownSettings {
+            dimensions: MIN_TOWN_DIMENSIONS,
+            ..settings
+        };
+        assert!(station_road(&smallest.generate_map().unwrap()).is_some());
+    }
+}
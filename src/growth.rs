@@ -0,0 +1,246 @@
+use super::*;
+use rand::Rng;
+
+/// Residents each house brings.
+pub const RESIDENTS_PER_HOUSE: i64 = 2;
+/// Jobs each factory offers.
+pub const JOBS_PER_FACTORY: i64 = 6;
+/// Jobs each general store or saloon offers.
+pub const JOBS_PER_SHOP: i64 = 2;
+/// Residents each general store or saloon serves at once, and so how many
+/// residents the town wants another one for.
+pub const CUSTOMERS_PER_SHOP: i64 = 8;
+/// Demand for houses even where there are no jobs, so that an empty map grows.
+const BASE_RESIDENTIAL_DEMAND: i64 = 2;
+/// How many of the best sites growth picks between.
+const SHORTLIST: usize = 5;
+/// Which of the generators seeded from the world seed growth draws from.
+const GROWTH_STREAM: u64 = 3;
+
+/// How many more buildings of each zone the town wants. Negative when there are
+/// too many already.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Demand {
+    pub residential: i64,
+    pub commercial: i64,
+    pub industrial: i64,
+}
+
+impl Demand {
+    /// Demand from the population and jobs of the buildings on `map`, by their
+    /// levels.
+    pub fn of(map: &Map) -> Demand {
+        let levels = |building| -> i64 {
+            map.buildings.get(&building).map_or(0, |origins| {
+                origins
+                    .iter()
+                    .filter_map(|origin| map.building_at(*origin))
+                    .map(|building_tile| building_tile.level as i64)
+                    .sum()
+            })
+        };
+        let places = |building| -> i64 {
+            map.buildings.get(&building).map_or(0, |origins| {
+                origins
+                    .iter()
+                    .filter_map(|origin| map.capacity(*origin))
+                    .sum::<u64>() as i64
+            })
+        };
+        let population = places(Building::House);
+        let shops = levels(Building::GeneralStore) + levels(Building::Saloon);
+        let shop_places = places(Building::GeneralStore) + places(Building::Saloon);
+        let jobs = places(Building::Factory) + shops * JOBS_PER_SHOP;
+        Demand {
+            residential: jobs - population + BASE_RESIDENTIAL_DEMAND,
+            commercial: (population - shop_places) / CUSTOMERS_PER_SHOP,
+            industrial: population - jobs,
+        }
+    }
+
+    pub fn of_zone(&self, zone: Zone) -> i64 {
+        match zone {
+            Zone::Residential => self.residential,
+            Zone::Commercial => self.commercial,
+            Zone::Industrial => self.industrial,
+        }
+    }
+
+    /// The zones wanting new buildings, most wanted first.
+    pub fn wanted(&self) -> Vec<Zone> {
+        let mut zones: Vec<Zone> = Zone::all()
+            .iter()
+            .cloned()
+            .filter(|z| self.of_zone(*z) > 0)
+            .collect();
+        zones.sort_by_key(|z| -self.of_zone(*z));
+        zones
+    }
+}
+
+/// Grows buildings on zoned land over time, according to `Demand`.
+#[derive(Clone, Debug)]
+pub struct Growth {
+    ticks_per_attempt: u64,
+    ticks: u64,
+    /// The world seed, from which the pick between the best sites follows.
+    seed: u64,
+    /// Attempts made so far, for seeding the next.
+    attempts: u64,
+}
+
+impl Growth {
+    /// Growth which tries to build once every `ticks_per_attempt` updates, picking
+    /// sites as `seed` decides.
+    pub fn new(ticks_per_attempt: u64, seed: u64) -> Growth {
+        Growth {
+            ticks_per_attempt,
+            ticks: 0,
+            seed,
+            attempts: 0,
+        }
+    }
+
+    pub fn save(&self, writer: &mut SaveWriter) {
+        writer.record(&[
+            "growth".to_string(),
+            self.ticks_per_attempt.to_string(),
+            self.ticks.to_string(),
+            self.seed.to_string(),
+            self.attempts.to_string(),
+        ]);
+    }
+
+    pub fn load(reader: &mut SaveReader) -> Result<Growth, SaveError> {
+        let (line, fields) = reader.expect("growth")?;
+        let mut growth = Growth::new(
+            reader.parse(line, &fields, 0)?,
+            reader.parse(line, &fields, 2)?,
+        );
+        growth.ticks = reader.parse(line, &fields, 1)?;
+        growth.attempts = reader.parse(line, &fields, 3)?;
+        Ok(growth)
+    }
+
+    /// Advance one tick. When an attempt is due, returns where the most wanted
+    /// building with room to grow should go and what it is, for the caller to build.
+    pub fn update(&mut self, map: &Map) -> Option<(Coord2, Building)> {
+        self.ticks += 1;
+        if self.ticks < self.ticks_per_attempt {
+            return None;
+        }
+        self.ticks = 0;
+        let mut rng = seeded_rng(&[self.seed, GROWTH_STREAM, self.attempts]);
+        self.attempts += 1;
+        for zone in Demand::of(map).wanted() {
+            let building = Growth::building_for(map, zone);
+            let sites = Growth::shortlist(map, zone, Growth::sites(map, zone, building));
+            if let Some(site) = rng.choose(&sites) {
+                return Some((*site, building));
+            }
+        }
+        None
+    }
+
+    /// Of the buildings `zone` grows, the one the town has fewest of.
+    pub fn building_for(map: &Map, zone: Zone) -> Building {
+        zone.buildings()
+            .into_iter()
+            .min_by_key(|b| map.buildings.get(b).map_or(0, |v| v.len()))
+            .unwrap()
+    }
+
+    /// The best of `sites` for `zone`. Homes and shops want the most valuable land,
+    /// and factories the least.
+    fn shortlist(map: &Map, zone: Zone, mut sites: Vec<Coord2>) -> Vec<Coord2> {
+        sites.sort_by(|a, b| {
+            map.land_value(*b)
+                .partial_cmp(&map.land_value(*a))
+                .unwrap()
+        });
+        if zone == Zone::Industrial {
+            sites.reverse();
+        }
+        sites.truncate(SHORTLIST);
+        sites
+    }
+
+    /// Every origin where `building` could be built wholly within `zone`.
+    pub fn sites(map: &Map, zone: Zone, building: Building) -> Vec<Coord2> {
+        map.zoned(zone)
+            .into_iter()
+            .filter(|origin| {
+                map.footprint(*origin, building)
+                    .iter()
+                    .all(|part| map.zone(*part) == Some(zone))
+                    && map.can_build(*origin, building)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demand_rises_with_unfilled_jobs_and_homes() {
+        let mut map = Map::new(Coord2 { x: 20, y: 20 });
+        for y in 3..18 {
+            map.pave(Coord2 { x: 10, y }).unwrap();
+        }
+        let empty = Demand::of(&map);
+        assert_eq!(
+            empty,
+            Demand {
+                residential: BASE_RESIDENTIAL_DEMAND,
+                commercial: 0,
+                industrial: 0,
+            }
+        );
+        assert_eq!(empty.wanted(), vec![Zone::Residential]);
+
+        map.build(Coord2 { x: 12, y: 4 }, Building::Factory).unwrap();
+        let with_jobs = Demand::of(&map);
+        assert_eq!(
+            with_jobs.residential,
+            BASE_RESIDENTIAL_DEMAND + JOBS_PER_FACTORY
+        );
+        assert!(with_jobs.industrial < 0);
+
+        for y in (9..17).step_by(2) {
+            map.build(Coord2 { x: 8, y }, Building::House).unwrap();
+        }
+        let with_homes = Demand::of(&map);
+        assert!(with_homes.residential < with_jobs.residential);
+        assert!(with_homes.commercial > 0);
+        assert!(with_homes.industrial > with_jobs.industrial);
+    }
+
+    #[test]
+    fn growth_builds_the_wanted_building_on_zoned_land_by_a_road() {
+        let mut map = map_with_street(&[]);
+        for y in 3..10 {
+            for x in 12..14 {
+                map.set_zone(Coord2 { x, y }, Some(Zone::Residential))
+                    .unwrap();
+            }
+        }
+        let mut growth = Growth::new(2, 0);
+        assert_eq!(growth.update(&map), None);
+        let (site, building) = growth.update(&map).unwrap();
+        assert_eq!(building, Building::House);
+        assert_eq!(map.zone(site), Some(Zone::Residential));
+        map.build(site, building).unwrap();
+        let entrance = map.building_at(site).unwrap().entryway_pos;
+        assert!(entrance
+            .neighbours()
+            .iter()
+            .any(|n| map.get(*n).and_then(Tile::as_paving).is_some()));
+
+        // Nothing grows without zoned land to grow on.
+        let unzoned = Map::new(Coord2 { x: 20, y: 20 });
+        let mut growth = Growth::new(1, 0);
+        assert_eq!(growth.update(&unzoned), None);
+    }
+}
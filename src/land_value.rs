@@ -0,0 +1,169 @@
+use super::*;
+use std::cmp::max;
+
+/// Value of land with nothing nearby, good or bad.
+pub const BASE_LAND_VALUE: f64 = 0.25;
+/// Furthest, by the cost of walking there, that a general store or saloon adds value.
+const AMENITY_REACH: f64 = 60.0;
+/// Furthest, by the cost of walking there, that a train station adds value.
+const STATION_REACH: f64 = 100.0;
+/// Furthest, in tiles, that a factory takes value away.
+const FACTORY_REACH: u64 = 5;
+
+/// Most that being right by each kind of building adds to, or takes from, land value.
+const STORE_WEIGHT: f64 = 0.25;
+const SALOON_WEIGHT: f64 = 0.2;
+const STATION_WEIGHT: f64 = 0.3;
+const FACTORY_PENALTY: f64 = 0.5;
+
+/// How desirable each tile is to live on, from 0 to 1. Land is worth more the
+/// easier it is to walk to general stores, saloons and train stations, and less
+/// the closer it is to a factory.
+#[derive(Clone, Debug)]
+pub struct LandValue {
+    /// Values which were surveyed. Anywhere else is at `BASE_LAND_VALUE`.
+    values: Grid<f64>,
+}
+
+impl LandValue {
+    /// Land values before any survey, all at `BASE_LAND_VALUE`.
+    pub fn new(dimensions: Coord2) -> LandValue {
+        LandValue {
+            values: Grid::new(dimensions),
+        }
+    }
+
+    /// Work out the value of every tile on `map`, and of the empty land within
+    /// `ZONE_REACH` of its roads. Empty land is reached from its nearest road.
+    pub fn survey(map: &Map) -> LandValue {
+        let dimensions = Coord2 {
+            x: map.width(),
+            y: map.height(),
+        };
+        let amenities = [
+            (Building::GeneralStore, AMENITY_REACH, STORE_WEIGHT),
+            (Building::Saloon, AMENITY_REACH, SALOON_WEIGHT),
+            (Building::TrainStation, STATION_REACH, STATION_WEIGHT),
+        ];
+        let costs: Vec<(Grid<f64>, f64, f64)> = amenities
+            .iter()
+            .map(|&(building, reach, weight)| {
+                let sources = map.buildings.get(&building).cloned().unwrap_or_default();
+                (costs_from(map, &sources, reach), reach, weight)
+            })
+            .collect();
+        let nuisance = factory_nuisance(map);
+
+        let mut values = Grid::new(dimensions);
+        for (location, _) in surveyed_locations(map, &nuisance).iter() {
+            let access = match map.get(location) {
+                Some(_) => Some((location, 0)),
+                None => map.nearest_road(location),
+            };
+            let mut value = BASE_LAND_VALUE;
+            if let Some((access, steps)) = access {
+                for &(ref costs, reach, weight) in &costs {
+                    if let Some(cost) = costs.get(access) {
+                        let cost = cost + steps as f64;
+                        value += weight * (1.0 - cost / reach).max(0.0);
+                    }
+                }
+            }
+            value -= FACTORY_PENALTY * nuisance.get(location).cloned().unwrap_or(0.0);
+            values.insert(location, value.max(0.0).min(1.0));
+        }
+        LandValue { values }
+    }
+
+    pub fn get(&self, location: Coord2) -> f64 {
+        self.values
+            .get(location)
+            .cloned()
+            .unwrap_or(BASE_LAND_VALUE)
+    }
+}
+
+/// How much factories spoil each location near them, from 0 to 1, falling away
+/// with distance from the nearest factory tile.
+fn factory_nuisance(map: &Map) -> Grid<f64> {
+    let mut nuisance = Grid::new(Coord2 {
+        x: map.width(),
+        y: map.height(),
+    });
+    let reach = FACTORY_REACH as i64;
+    let factories = map.buildings.get(&Building::Factory).cloned().unwrap_or_default();
+    for origin in factories {
+        for part in map.building_tiles(origin) {
+            for dy in -reach..reach + 1 {
+                for dx in -reach..reach + 1 {
+                    let (x, y) = (part.x as i64 + dx, part.y as i64 + dy);
+                    let location = Coord2 {
+                        x: x as u64,
+                        y: y as u64,
+                    };
+                    if x < 0 || y < 0 || !map.is_bounded(location) {
+                        continue;
+                    }
+                    let distance = max(dx.abs(), dy.abs()) as f64;
+                    let amount = 1.0 - distance / (reach + 1) as f64;
+                    if nuisance.get(location).map_or(true, |&n| amount > n) {
+                        nuisance.insert(location, amount);
+                    }
+                }
+            }
+        }
+    }
+    nuisance
+}
+
+/// Every location worth surveying: tiles, empty land near roads, and land near
+/// factories.
+fn surveyed_locations(map: &Map, nuisance: &Grid<f64>) -> Grid<()> {
+    let mut locations = Grid::new(Coord2 {
+        x: map.width(),
+        y: map.height(),
+    });
+    for (location, tile) in map.tiles() {
+        locations.insert(location, ());
+        if tile.as_paving().is_none() {
+            continue;
+        }
+        for &direction in Direction::all().iter() {
+            let mut reached = location;
+            for _ in 0..ZONE_REACH {
+                reached = match reached.step(direction) {
+                    Some(l) if map.is_bounded(l) => l,
+                    _ => break,
+                };
+                locations.insert(reached, ());
+            }
+        }
+    }
+    for (location, _) in nuisance.iter() {
+        locations.insert(location, ());
+    }
+    locations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stores_raise_and_factories_lower_land_value() {
+        let mut map = Map::new(Coord2 { x: 20, y: 20 });
+        assert_eq!(map.land_value(Coord2 { x: 7, y: 3 }), BASE_LAND_VALUE);
+        for x in (3..10).rev().chain(11..18) {
+            map.pave(Coord2 { x, y: 2 }).unwrap();
+        }
+        map.build(Coord2 { x: 4, y: 4 }, Building::GeneralStore)
+            .unwrap();
+        map.build(Coord2 { x: 14, y: 4 }, Building::Factory)
+            .unwrap();
+        map.survey_land_value();
+
+        assert!(map.land_value(Coord2 { x: 7, y: 3 }) > BASE_LAND_VALUE);
+        assert!(map.land_value(Coord2 { x: 13, y: 3 }) < BASE_LAND_VALUE);
+        assert_eq!(map.land_value(Coord2 { x: 0, y: 19 }), BASE_LAND_VALUE);
+    }
+}
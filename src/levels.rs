@@ -0,0 +1,184 @@
+use super::*;
+use std::collections::*;
+
+/// How much each review counts towards a building's sustained usage.
+const USAGE_WEIGHT: f64 = 0.1;
+/// Sustained usage, as a share of capacity, at which a building goes up a level.
+const BUSY_USAGE: f64 = 0.5;
+/// Sustained usage below which a building goes down a level.
+const NEGLECTED_USAGE: f64 = 0.1;
+/// Land value needed for each level above the first, except by factories.
+const LAND_VALUE_PER_LEVEL: f64 = 0.1;
+/// How far land value may fall short of a building's level before it goes down.
+const LAND_VALUE_SLACK: f64 = 0.05;
+
+/// Raises buildings a level with sustained use on valuable enough land, and lowers
+/// them with neglect.
+#[derive(Clone, Debug)]
+pub struct Levels {
+    ticks_per_review: u64,
+    ticks: u64,
+    /// Each building's usage as a share of its capacity, averaged over past reviews.
+    usage: HashMap<Coord2, f64>,
+}
+
+impl Levels {
+    /// Levels which are reviewed once every `ticks_per_review` updates.
+    pub fn new(ticks_per_review: u64) -> Levels {
+        Levels {
+            ticks_per_review,
+            ticks: 0,
+            usage: HashMap::new(),
+        }
+    }
+
+    /// Move every building reviewed by `offset`, as the map is resized.
+    pub fn shift(&mut self, offset: (i64, i64)) {
+        self.usage = self
+            .usage
+            .drain()
+            .map(|(origin, usage)| (origin.shifted(offset), usage))
+            .collect();
+    }
+
+    pub fn save(&self, writer: &mut SaveWriter) {
+        writer.record(&[
+            "levels".to_string(),
+            self.ticks_per_review.to_string(),
+            self.ticks.to_string(),
+            self.usage.len().to_string(),
+        ]);
+        let mut origins: Vec<&Coord2> = self.usage.keys().collect();
+        origins.sort_by_key(|o| (o.y, o.x));
+        for origin in origins {
+            writer.record(&[
+                "usage".to_string(),
+                origin.x.to_string(),
+                origin.y.to_string(),
+                self.usage[origin].to_string(),
+            ]);
+        }
+    }
+
+    pub fn load(reader: &mut SaveReader) -> Result<Levels, SaveError> {
+        let (line, fields) = reader.expect("levels")?;
+        let mut levels = Levels::new(reader.parse(line, &fields, 0)?);
+        levels.ticks = reader.parse(line, &fields, 1)?;
+        let count: usize = reader.parse(line, &fields, 2)?;
+        for _ in 0..count {
+            let (line, fields) = reader.expect("usage")?;
+            let origin = Coord2 {
+                x: reader.parse(line, &fields, 0)?,
+                y: reader.parse(line, &fields, 1)?,
+            };
+            levels.usage.insert(origin, reader.parse(line, &fields, 2)?);
+        }
+        Ok(levels)
+    }
+
+    /// Advance one tick. When a review is due, raise or lower the level of every
+    /// building which has earned it, returning what changed. A change the map
+    /// refuses is skipped, and left out.
+    pub fn update(&mut self, map: &mut Map, occupancy: &Occupancy) -> Vec<StatsEvent> {
+        self.ticks += 1;
+        if self.ticks < self.ticks_per_review {
+            return vec![];
+        }
+        self.ticks = 0;
+
+        let mut usage = HashMap::new();
+        let mut changes = vec![];
+        for origins in map.buildings.values() {
+            for &origin in origins {
+                let building_tile = match map.building_at(origin) {
+                    Some(building_tile) => building_tile,
+                    None => continue,
+                };
+                let capacity = match building_tile.capacity() {
+                    Some(capacity) => capacity,
+                    None => continue,
+                };
+                let share = occupancy.of(origin) as f64 / capacity as f64;
+                let sustained = self.usage.get(&origin).cloned().unwrap_or(0.0);
+                let sustained = sustained + (share - sustained) * USAGE_WEIGHT;
+                usage.insert(origin, sustained);
+
+                let (building, level) = (building_tile.building, building_tile.level);
+                let land_value = map.land_value(origin);
+                if level < MAX_BUILDING_LEVEL
+                    && sustained >= BUSY_USAGE
+                    && land_value >= land_value_needed(building, level + 1)
+                {
+                    changes.push(StatsEvent::Upgraded(origin, building, level + 1));
+                } else if level > 1
+                    && (sustained < NEGLECTED_USAGE
+                        || land_value < land_value_needed(building, level) - LAND_VALUE_SLACK)
+                {
+                    changes.push(StatsEvent::Downgraded(origin, building, level - 1));
+                }
+            }
+        }
+        // Forgetting demolished buildings along the way.
+        self.usage = usage;
+
+        changes.retain(|change| {
+            let result = match *change {
+                StatsEvent::Upgraded(origin, _, _) => map.upgrade_building(origin),
+                StatsEvent::Downgraded(origin, _, _) => map.downgrade_building(origin),
+            };
+            if let Err(ref e) = result {
+                eprintln!("Skipped a level change: {}", e);
+            }
+            result.is_ok()
+        });
+        changes
+    }
+}
+
+/// The land value `building` needs to stand at `level`. Factories need none.
+fn land_value_needed(building: Building, level: u64) -> f64 {
+    match building {
+        Building::Factory => 0.0,
+        _ => BASE_LAND_VALUE + LAND_VALUE_PER_LEVEL * (level - 1) as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn busy_buildings_go_up_and_neglected_ones_come_down() {
+        let factory = Coord2 { x: 12, y: 4 };
+        let mut map = map_with_street(&[(factory, Building::Factory)]);
+        let events = map.subscribe();
+        let mut levels = Levels::new(1);
+        let mut occupancy = Occupancy::default();
+        let workers = vec![factory; JOBS_PER_FACTORY as usize];
+
+        occupancy.take(&workers);
+        let mut changes = vec![];
+        while changes.is_empty() {
+            changes = levels.update(&mut map, &occupancy);
+        }
+        assert_eq!(
+            changes,
+            vec![StatsEvent::Upgraded(factory, Building::Factory, 2)]
+        );
+        assert_eq!(map.capacity(factory), Some(2 * JOBS_PER_FACTORY as u64));
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![MapEvent::LevelChanged(factory, Building::Factory, 2)]
+        );
+
+        occupancy.give_up(&workers);
+        changes.clear();
+        while changes.is_empty() {
+            changes = levels.update(&mut map, &occupancy);
+        }
+        assert_eq!(
+            changes,
+            vec![StatsEvent::Downgraded(factory, Building::Factory, 1)]
+        );
+    }
+}
@@ -4,18 +4,40 @@ extern crate rayon;
 extern crate uuid;
 
 mod agents;
+mod generate;
+mod growth;
+mod land_value;
+mod levels;
 mod map;
 mod render_to_piston;
 mod routing;
+mod save;
+mod scenario;
+mod simulation;
+mod stats;
+mod terrain;
 mod tile;
+mod zone;
 
 pub use agents::*;
+pub use generate::*;
+pub use growth::*;
+pub use land_value::*;
+pub use levels::*;
 pub use map::*;
 pub use render_to_piston::*;
 pub use routing::*;
+pub use save::*;
+pub use scenario::*;
+pub use simulation::*;
+pub use stats::*;
+pub use terrain::*;
 pub use tile::*;
+pub use zone::*;
 
 use piston_window::Key;
+use rand::prng::XorShiftRng;
+use rand::SeedableRng;
 use std::fmt;
 use std::ops::{Add, Mul, Sub};
 
@@ -61,6 +83,38 @@ impl Coord2 {
         neighbours.push(east);
         neighbours
     }
+
+    /// This location moved by `offset`, stopping at zero.
+    pub fn shifted(&self, offset: (i64, i64)) -> Coord2 {
+        Coord2 {
+            x: (self.x as i64 + offset.0).max(0) as u64,
+            y: (self.y as i64 + offset.1).max(0) as u64,
+        }
+    }
+
+    /// The neighbouring location in `direction`, unless that would go below zero.
+    pub fn step(&self, direction: Direction) -> Option<Coord2> {
+        match direction {
+            Direction::North if self.y == 0 => None,
+            Direction::West if self.x == 0 => None,
+            Direction::North => Some(Coord2 {
+                x: self.x,
+                y: self.y - 1,
+            }),
+            Direction::East => Some(Coord2 {
+                x: self.x + 1,
+                y: self.y,
+            }),
+            Direction::South => Some(Coord2 {
+                x: self.x,
+                y: self.y + 1,
+            }),
+            Direction::West => Some(Coord2 {
+                x: self.x - 1,
+                y: self.y,
+            }),
+        }
+    }
 }
 
 impl Add for Coord2 {
@@ -146,6 +200,31 @@ pub enum Direction {
 }
 
 impl Direction {
+    pub fn all() -> [Direction; 4] {
+        [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ]
+    }
+
+    pub fn opposite(&self) -> Direction {
+        match *self {
+            Direction::North => Direction::South,
+            Direction::East => Direction::West,
+            Direction::South => Direction::North,
+            Direction::West => Direction::East,
+        }
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        match *self {
+            Direction::North | Direction::South => Orientation::Vertical,
+            Direction::East | Direction::West => Orientation::Horizontal,
+        }
+    }
+
     pub fn between_coord2s(a: Coord2, b: Coord2) -> Option<Direction> {
         if (a.x == b.x && a.y == b.y) || (a.x != b.x && a.y != b.y) {
             None
@@ -170,6 +249,25 @@ impl Direction {
             Direction::West => (-1, 0),
         }
     }
+
+    pub fn from_name(name: &str) -> Option<Direction> {
+        match name {
+            "north" => Some(Direction::North),
+            "east" => Some(Direction::East),
+            "south" => Some(Direction::South),
+            "west" => Some(Direction::West),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Direction::North => "north",
+            Direction::East => "east",
+            Direction::South => "south",
+            Direction::West => "west",
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -224,6 +322,48 @@ impl Path {
         self.start + self.orientation.as_offset() * self.length
     }
 
+    /// The straight path covering `a`, `b` and every tile between them, if they
+    /// share a row or column.
+    pub fn between(a: Coord2, b: Coord2) -> Option<Path> {
+        let orientation = if a == b {
+            Orientation::Horizontal
+        } else {
+            Orientation::between_coord2s(a, b)?
+        };
+        let start = Coord2 {
+            x: ::std::cmp::min(a.x, b.x),
+            y: ::std::cmp::min(a.y, b.y),
+        };
+        let length = a.x.max(b.x) - start.x + a.y.max(b.y) - start.y + 1;
+        Some(Path {
+            start,
+            orientation,
+            length,
+        })
+    }
+
+    /// Paths from `from` along its row to the column of `to`, then along that column
+    /// to `to`.
+    pub fn l_shaped(from: Coord2, to: Coord2) -> Vec<Path> {
+        let corner = Coord2 {
+            x: to.x,
+            y: from.y,
+        };
+        vec![
+            Path::between(from, corner).unwrap(),
+            Path::between(corner, to).unwrap(),
+        ]
+    }
+
+    /// Straight paths joining each of `points` to the next, if each shares a row or
+    /// column with the next.
+    pub fn polyline(points: &[Coord2]) -> Option<Vec<Path>> {
+        points
+            .windows(2)
+            .map(|pair| Path::between(pair[0], pair[1]))
+            .collect()
+    }
+
     pub fn advance(self) -> Option<Path> {
         let new_path = Path {
             start: self.start + self.orientation.as_offset(),
@@ -257,7 +397,7 @@ impl IntoIterator for Path {
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Building {
     House,
     Saloon,
@@ -289,6 +429,51 @@ impl Building {
         }
     }
 
+    /// Width and height in tiles. Stations are turned to run along horizontal track.
+    pub fn footprint(&self) -> Coord2 {
+        match *self {
+            Building::House => Coord2 { x: 1, y: 1 },
+            Building::Saloon => Coord2 { x: 2, y: 2 },
+            Building::Factory => Coord2 { x: 2, y: 3 },
+            Building::GeneralStore => Coord2 { x: 2, y: 2 },
+            Building::TrainStation => Coord2 { x: 1, y: 4 },
+        }
+    }
+
+    /// How many residents the building has places for at each level: living in a
+    /// house, working in a factory, or served at once in a general store or saloon.
+    /// Stations take any number.
+    pub fn capacity(&self) -> Option<u64> {
+        match *self {
+            Building::House => Some(RESIDENTS_PER_HOUSE as u64),
+            Building::Saloon => Some(CUSTOMERS_PER_SHOP as u64),
+            Building::Factory => Some(JOBS_PER_FACTORY as u64),
+            Building::GeneralStore => Some(CUSTOMERS_PER_SHOP as u64),
+            Building::TrainStation => None,
+        }
+    }
+
+    /// What the building brings in at each level, every time income is collected.
+    pub fn income(&self) -> u64 {
+        match *self {
+            Building::House => 1,
+            Building::Saloon => 3,
+            Building::Factory => 5,
+            Building::GeneralStore => 3,
+            Building::TrainStation => 0,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Building::House => "house",
+            Building::Saloon => "saloon",
+            Building::Factory => "factory",
+            Building::GeneralStore => "general store",
+            Building::TrainStation => "train station",
+        }
+    }
+
     pub fn code(&self) -> char {
         match *self {
             Building::House => 'h',
@@ -306,6 +491,30 @@ impl fmt::Display for Building {
     }
 }
 
+/// A generator seeded from `words`, such as a world seed and what the numbers are
+/// for, which gives the same numbers whenever the words are the same.
+pub fn seeded_rng(words: &[u64]) -> XorShiftRng {
+    let mut state = 0;
+    for &word in words {
+        state = split_mix(state ^ word);
+    }
+    let halves = [state, split_mix(state)];
+    let mut seed = [0; 16];
+    for (i, byte) in seed.iter_mut().enumerate() {
+        *byte = (halves[i / 8] >> (8 * (i % 8))) as u8;
+    }
+    XorShiftRng::from_seed(seed)
+}
+
+/// One step of SplitMix64, which scrambles nearby words, such as successive ticks,
+/// into unrelated ones.
+fn split_mix(word: u64) -> u64 {
+    let mut z = word.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -1,158 +1,209 @@
+#[macro_use]
 extern crate clap;
 extern crate piston_window;
+extern crate rand;
 extern crate tilewater;
 
 //use std::thread;
 //use std::time::Duration;
-use std::sync::{mpsc, Arc, RwLock};
-//use clap::{Arg, App};
+use clap::{App, Arg, ArgMatches, SubCommand};
 use piston_window::*;
+use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::{mpsc, Arc, RwLock};
 use tilewater::*;
 
+/// Updates per second, with or without a window.
+const UPS: u64 = 40;
+
 fn main() {
+    let matches = App::new("Tilewater")
+        .about("A miniature city simulator.")
+        .arg(
+            Arg::with_name("save")
+                .long("save")
+                .value_name("FILE")
+                .help("Resume from FILE if it exists, and save to it when the window closes")
+                .takes_value(true)
+                .validator(save_path),
+        )
+        .args(&city_args())
+        .subcommand(
+            SubCommand::with_name("simulate")
+                .about("Run the city without a window, printing statistics at the end.")
+                .arg(
+                    Arg::with_name("ticks")
+                        .long("ticks")
+                        .value_name("TICKS")
+                        .help("Run for TICKS updates")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .args(&city_args()),
+        )
+        .get_matches();
+    match matches.subcommand_matches("simulate") {
+        Some(matches) => simulate(matches),
+        None => play(&matches),
+    }
+}
+
+/// Where the city starts from, shared by playing and simulating.
+fn city_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("scenario")
+            .long("scenario")
+            .value_name("FILE")
+            .help("Start from the city described in FILE rather than a generated town")
+            .takes_value(true)
+            .conflicts_with("seed"),
+        Arg::with_name("seed")
+            .long("seed")
+            .value_name("SEED")
+            .help("Generate the town from SEED rather than a random one")
+            .takes_value(true),
+        Arg::with_name("layout")
+            .long("layout")
+            .value_name("LAYOUT")
+            .help("Lay out the streets of a generated town as a grid or organically")
+            .possible_values(&["grid", "organic"])
+            .default_value("grid"),
+    ]
+}
+
+fn play(matches: &ArgMatches) {
+    let save_path = matches.value_of("save");
+
+    let (passenger_tx, passenger_rx) = mpsc::channel();
+    let simulation = match save_path.filter(|path| Path::new(path).exists()) {
+        Some(path) => {
+            let text = fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("Could not read {}: {}", path, e);
+                process::exit(1)
+            });
+            load_game(
+                &text,
+                &DeciderRegistry::default(),
+                passenger_tx,
+                passenger_rx,
+            )
+            .unwrap_or_else(|e| {
+                eprintln!("Could not load {}: {}", path, e);
+                process::exit(1)
+            })
+        }
+        None => start(matches, passenger_tx, passenger_rx),
+    };
+    repair(&simulation);
+
     let mut window: PistonWindow = WindowSettings::new("Tilewater", [800, 800])
         .exit_on_esc(true)
         .build()
         .unwrap();
 
-    let ups = 40;
-    window.set_ups(ups);
+    window.set_ups(UPS);
     window.set_max_fps(60);
-    let mut agents = Agents::new(ups / 5);
-
-    let mut map = Map::new(Coord2 { x: 80, y: 80 });
 
-    for y in 1..31 {
-        let p = Coord2 { x: 40, y: y };
-        if map.can_pave(p) {
-            map.pave(p);
-        }
-    }
-    for x in 40..49 {
-        let p = Coord2 { x: x, y: 3 };
-        if map.can_pave(p) {
-            map.pave(p);
-        }
-    }
-    for y in 3..16 {
-        let p = Coord2 { x: 48, y: y };
-        if map.can_pave(p) {
-            map.pave(p);
-        }
-    }
-    for x in 48..56 {
-        let p = Coord2 { x: x, y: 6 };
-        if map.can_pave(p) {
-            map.pave(p);
+    let mut renderer = RenderToPiston::new(simulation, window);
+    renderer.render_loop();
+    if let Some(path) = save_path {
+        if let Err(e) = fs::write(path, renderer.save()) {
+            eprintln!("Could not save to {}: {}", path, e);
+            process::exit(1)
         }
     }
-    for y in 6..21 {
-        let p = Coord2 { x: 55, y: y };
-        if map.can_pave(p) {
-            map.pave(p);
-        }
-    }
-    for x in 55..69 {
-        let p = Coord2 { x: x, y: 9 };
-        if map.can_pave(p) {
-            map.pave(p);
-        }
+}
+
+/// Refuses a save file which could never be written, so that it is found out
+/// before playing rather than when the window closes.
+fn save_path(path: String) -> Result<(), String> {
+    let path = Path::new(&path);
+    if path.is_dir() {
+        return Err(format!("{} is a directory", path.display()));
     }
-    for x in 40..65 {
-        let p = Coord2 { x: x, y: 20 };
-        if map.can_pave(p) {
-            map.pave(p);
+    match path.parent() {
+        Some(parent) if parent != Path::new("") && !parent.is_dir() => {
+            Err(format!("there is no directory {}", parent.display()))
         }
+        _ => Ok(()),
     }
+}
 
-    for x in 0..80 {
-        let c = Coord2 { x: x, y: 0 };
-        //if map.can_rail(c) {
-        map.rail(c);
-        //}
-    }
-
-    let bs = vec![
-        (42, 4, 'f'),
-        (42, 6, 'f'),
-        (38, 9, 'f'),
-        (42, 9, 's'),
-        (42, 11, 'f'),
-        (42, 13, 'f'),
-        (42, 15, 'f'),
-        (42, 17, 'h'),
-        (38, 17, 'g'),
-        (44, 4, 'h'),
-        (46, 4, 'h'),
-        (46, 7, 'h'),
-        (46, 9, 'h'),
-        (46, 11, 'h'),
-        (46, 13, 'h'),
-        (48, 16, 'h'),
-        (50, 8, 'h'),
-        (50, 10, 'h'),
-        (50, 12, 'h'),
-        (50, 14, 'h'),
-        (50, 3, 'h'),
-        (52, 3, 'h'),
-        (54, 3, 'h'),
-        (57, 6, 's'),
-        (59, 6, 'g'),
-        (57, 10, 'h'),
-        (59, 10, 'h'),
-        (57, 12, 'h'),
-        (57, 14, 'h'),
-        (57, 17, 'h'),
-        (59, 17, 'h'),
-        (44, 17, 'h'),
-        (46, 17, 'h'),
-        (49, 17, 'h'),
-        (51, 17, 'h'),
-        (53, 17, 'h'),
-    ];
+/// Run the city for `--ticks` updates with no window, then print where it stands.
+fn simulate(matches: &ArgMatches) {
+    let ticks = value_t!(matches, "ticks", u64).unwrap_or_else(|e| e.exit());
 
     let (passenger_tx, passenger_rx) = mpsc::channel();
-    for b in bs {
-        let c = Coord2 { x: b.0, y: b.1 + 1 };
-        if map.can_build(c) {
-            let building = Building::from_code(b.2).unwrap();
-            map.build(c, building);
-            if building == Building::House {
-                let decider = ResidentDecider::new(c);
-                let agent = Agent::new(Coord2 { x: 40, y: 2 }, Box::new(decider));
-                //agents.insert(AgentKind::Resident, agent);
-                passenger_tx.send(agent).unwrap();
-            }
-        } else {
-            println!("{:?}", c);
-            panic!("Unbuildable seed building.");
+    let mut simulation = start(matches, passenger_tx, passenger_rx);
+    repair(&simulation);
+
+    for _ in 0..ticks {
+        for event in simulation.update() {
+            println!("{}", event);
         }
     }
+    println!("{}", simulation.report());
+}
 
-    let train_decider = TrainDecider::new(Coord2 { x: 42, y: 0 }, passenger_rx);
-    let train_agent = Agent::new(Coord2 { x: 0, y: 0 }, Box::new(train_decider));
-    agents.insert(AgentKind::Train, train_agent);
-    //println!("{}", map);
-
-    let map = Arc::new(RwLock::new(map));
-    RenderToPiston::new(agents, window, map, passenger_tx).render_loop();
-
-    // for i in 0..20 {
-    //     let p = Coord2 { x: 40, y: i + 1 };
-    //     if map.can_pave(p) {
-    //         map.pave(p);
-    //     }
-
-    //     if i >= 2 {
-    //         let b = Coord2 { x: 42, y: i - 2 };
-    //         if map.can_build(b) {
-    //             map.build(b, Building::new('h'));
-    //         }
-    //     }
+/// The scenario named by `--scenario`, or else a town generated from `--seed`.
+fn start(
+    matches: &ArgMatches,
+    passenger_tx: mpsc::Sender<Agent>,
+    passenger_rx: mpsc::Receiver<Agent>,
+) -> Simulation {
+    let (map, agents, spawn) = match matches.value_of("scenario") {
+        Some(path) => {
+            let text = fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("Could not read scenario {}: {}", path, e);
+                process::exit(1)
+            });
+            let scenario: Scenario = text.parse().unwrap_or_else(|e| {
+                eprintln!("Could not load scenario {}: {}", path, e);
+                process::exit(1)
+            });
+            let agents = Agents::new(UPS / 5, scenario.seed);
+            scenario.start(agents, &passenger_tx, passenger_rx)
+        }
+        None => {
+            let seed = if matches.is_present("seed") {
+                value_t!(matches, "seed", u64).unwrap_or_else(|e| e.exit())
+            } else {
+                rand::random()
+            };
+            let layout = matches
+                .value_of("layout")
+                .and_then(StreetLayout::from_name)
+                .unwrap_or_else(|| {
+                    eprintln!("LAYOUT must be grid or organic");
+                    process::exit(1)
+                });
+            let settings = TownSettings {
+                seed,
+                layout,
+                ..TownSettings::default()
+            };
+            println!("Generating a town from seed {}", settings.seed);
+            let agents = Agents::new(UPS / 5, settings.seed);
+            let (map, agents) = settings
+                .generate(agents, &passenger_tx, passenger_rx)
+                .unwrap_or_else(|e| {
+                    eprintln!("Could not generate a town: {}", e);
+                    process::exit(1)
+                });
+            (map, agents, None)
+        }
+    };
+    let mut simulation = Simulation::new(agents, Arc::new(RwLock::new(map)), passenger_tx);
+    if spawn.is_some() {
+        simulation.spawn = spawn;
+    }
+    simulation
+}
 
-    //     println!("{}", map);
-    //     std::io::stdout().flush().unwrap();
-    //     thread::sleep(Duration::from_millis(100));
-    //     println!("\n\n\n\n");
-    // }
+/// Fix up whatever in the map is inconsistent, saying what was.
+fn repair(simulation: &Simulation) {
+    for problem in simulation.map.write().unwrap().repair() {
+        println!("Repaired map: {}", problem);
+    }
 }
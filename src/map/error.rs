@@ -0,0 +1,93 @@
+use super::*;
+use std::error::Error;
+
+/// Why an edit to a `Map` could not be made.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MapError {
+    /// The location lies outside the map's dimensions.
+    OutOfBounds(Coord2),
+    /// A building has no road two tiles away to build an entrance from.
+    NoAdjacentRoad(Coord2),
+    /// The location, or a neighbour which must be clear, is already taken by a tile.
+    Occupied(Coord2),
+    /// A station must be placed on a straight run of rails.
+    NotOnRails(Coord2),
+    /// The edit would leave the building at this location without an entrance.
+    WouldOrphanBuilding(Coord2),
+    /// A link between tiles, recorded at this location, does not match the tiles.
+    InconsistentAdjacency(Coord2),
+    /// The ground at the location cannot take what was asked of it.
+    UnsuitableTerrain(Coord2, Terrain),
+    /// Zones must be within reach of a road.
+    NoNearbyRoad(Coord2),
+    /// The tile at this location has changed since the edit being undone or redone.
+    ChangedSince(Coord2),
+    /// Only paving has a road class.
+    NotPaved(Coord2),
+    /// The road is already of the highest or lowest class.
+    NoFurtherRoadClass(Coord2, RoadClass),
+    /// Maps of these dimensions leave no room for what was asked of them.
+    TooSmall(Coord2),
+    /// Maps may be no wider or taller than `MAX_MAP_DIMENSION`.
+    TooLarge(Coord2),
+    /// Shrinking the map would cut off the tile at this location.
+    WouldCutOff(Coord2),
+    /// No building has the north-west corner of its footprint at this location.
+    NoBuilding(Coord2),
+    /// The building is already of the highest or lowest level.
+    NoFurtherLevel(Coord2, u64),
+    /// The map does not have at this location what the patch expects to change.
+    PatchMismatch(Coord2),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MapError::OutOfBounds(l) => write!(f, "({}, {}) is off the map", l.x, l.y),
+            MapError::NoAdjacentRoad(l) => {
+                write!(f, "({}, {}) has no road to build an entrance from", l.x, l.y)
+            }
+            MapError::Occupied(l) => write!(f, "({}, {}) is occupied", l.x, l.y),
+            MapError::NotOnRails(l) => write!(f, "({}, {}) is not straight rails", l.x, l.y),
+            MapError::WouldOrphanBuilding(l) => {
+                write!(f, "the building at ({}, {}) would lose its entrance", l.x, l.y)
+            }
+            MapError::InconsistentAdjacency(l) => {
+                write!(f, "the tile links at ({}, {}) are inconsistent", l.x, l.y)
+            }
+            MapError::UnsuitableTerrain(l, terrain) => {
+                write!(f, "({}, {}) is {}, which is unsuitable", l.x, l.y, terrain)
+            }
+            MapError::NoNearbyRoad(l) => write!(f, "({}, {}) is too far from a road", l.x, l.y),
+            MapError::ChangedSince(l) => {
+                write!(f, "({}, {}) has changed since that edit", l.x, l.y)
+            }
+            MapError::NotPaved(l) => write!(f, "({}, {}) is not paved", l.x, l.y),
+            MapError::NoFurtherRoadClass(l, class) => {
+                write!(f, "the {} at ({}, {}) cannot change class further", class, l.x, l.y)
+            }
+            MapError::TooSmall(d) => write!(f, "a map of {} by {} is too small", d.x, d.y),
+            MapError::TooLarge(d) => write!(
+                f,
+                "a map of {} by {} is larger than {} on a side",
+                d.x, d.y, MAX_MAP_DIMENSION
+            ),
+            MapError::WouldCutOff(l) => write!(f, "({}, {}) would be cut off the map", l.x, l.y),
+            MapError::NoBuilding(l) => write!(f, "no building starts at ({}, {})", l.x, l.y),
+            MapError::NoFurtherLevel(l, level) => write!(
+                f,
+                "the building at ({}, {}) cannot go further than level {}",
+                l.x, l.y, level
+            ),
+            MapError::PatchMismatch(l) => {
+                write!(f, "({}, {}) is not as the patch expects", l.x, l.y)
+            }
+        }
+    }
+}
+
+impl Error for MapError {
+    fn description(&self) -> &str {
+        "invalid map edit"
+    }
+}
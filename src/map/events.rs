@@ -0,0 +1,159 @@
+use super::*;
+use std::sync::mpsc;
+
+/// A change to the map, sent to every subscriber as it is made.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum MapEvent {
+    TilePaved(Coord2),
+    PavingRemoved(Coord2),
+    RoadClassChanged(Coord2, RoadClass),
+    RailLaid(Coord2),
+    RailsRemoved(Coord2),
+    /// A building was built with the north-west corner of its footprint here.
+    BuildingBuilt(Coord2, Building),
+    /// The building with the north-west corner of its footprint here was deleted.
+    BuildingDeleted(Coord2, Building),
+    /// The building with the north-west corner of its footprint here went up or
+    /// down to the level given.
+    LevelChanged(Coord2, Building, u64),
+}
+
+/// The senders handed out by `Map::subscribe`. A clone of a map starts without any,
+/// so that its edits are not mistaken for the original's.
+#[derive(Debug, Default)]
+pub(super) struct Subscribers(Vec<mpsc::Sender<MapEvent>>);
+
+impl Clone for Subscribers {
+    fn clone(&self) -> Subscribers {
+        Subscribers::default()
+    }
+}
+
+impl Map {
+    /// Receive a `MapEvent` for every change made to the map from now on.
+    pub fn subscribe(&mut self) -> mpsc::Receiver<MapEvent> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.0.push(tx);
+        rx
+    }
+
+    /// Send `event` to every subscriber, forgetting those which have hung up. While
+    /// an edit is being journalled the event is held until it is committed.
+    pub(super) fn emit(&mut self, event: MapEvent) {
+        match self.journal {
+            Some(ref mut journal) => journal.events.push(event),
+            None => self.subscribers.0.retain(|tx| tx.send(event).is_ok()),
+        }
+    }
+
+    /// Emit the events for the tile at `location` changing from `before` to what it
+    /// is now, for changes made without the usual rules, such as undoing.
+    pub(super) fn emit_changed(&mut self, location: Coord2, before: Option<&Tile>) {
+        let after = self.get(location).cloned();
+        for event in tile_events(location, before, after.as_ref()) {
+            self.emit(event);
+        }
+    }
+}
+
+/// What changed between two versions of the tile at `location`.
+fn tile_events(location: Coord2, before: Option<&Tile>, after: Option<&Tile>) -> Vec<MapEvent> {
+    let mut events = vec![];
+    let class = |tile: Option<&Tile>| tile.and_then(Tile::as_paving).map(|p| p.class);
+    match (class(before), class(after)) {
+        (None, Some(_)) => events.push(MapEvent::TilePaved(location)),
+        (Some(_), None) => events.push(MapEvent::PavingRemoved(location)),
+        (Some(old), Some(new)) if old != new => {
+            events.push(MapEvent::RoadClassChanged(location, new))
+        }
+        _ => {}
+    }
+    let rails = |tile: Option<&Tile>| tile.and_then(Tile::as_rails).is_some();
+    match (rails(before), rails(after)) {
+        (false, true) => events.push(MapEvent::RailLaid(location)),
+        (true, false) => events.push(MapEvent::RailsRemoved(location)),
+        _ => {}
+    }
+    // A building is built or deleted where the corner of its footprint changes.
+    let building = |tile: Option<&Tile>| {
+        let building_tile = match tile {
+            Some(&Tile::Building(ref building_tile)) => building_tile,
+            Some(&Tile::Rails(RailsTile {
+                station: Some(ref building_tile),
+                ..
+            })) => building_tile,
+            _ => return None,
+        };
+        Some((building_tile.building, building_tile.level))
+            .filter(|_| building_tile.origin == location)
+    };
+    let (built_before, built_after) = (building(before), building(after));
+    match (built_before, built_after) {
+        (Some((old, old_level)), Some((new, level))) if old == new => {
+            if old_level != level {
+                events.push(MapEvent::LevelChanged(location, new, level));
+            }
+        }
+        _ => {
+            if let Some((building, _)) = built_before {
+                events.push(MapEvent::BuildingDeleted(location, building));
+            }
+            if let Some((building, _)) = built_after {
+                events.push(MapEvent::BuildingBuilt(location, building));
+            }
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_and_undoing_them_are_sent_to_subscribers() {
+        let mut map = Map::new(Coord2 { x: 20, y: 20 });
+        let mut history = History::new(10);
+        let events = map.subscribe();
+        let (start, road) = (Coord2 { x: 10, y: 2 }, Coord2 { x: 10, y: 3 });
+        let house = Coord2 { x: 12, y: 2 };
+        history.apply(&mut map, Edit::Pave(road)).unwrap();
+        history.apply(&mut map, Edit::Build(house, Building::House, None)).unwrap();
+        history.apply(&mut map, Edit::Delete(start)).unwrap();
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            vec![
+                MapEvent::TilePaved(road),
+                MapEvent::BuildingBuilt(house, Building::House),
+                MapEvent::BuildingDeleted(house, Building::House),
+                MapEvent::PavingRemoved(start),
+            ]
+        );
+
+        history.undo(&mut map).unwrap();
+        let undone = events.try_iter().collect::<Vec<_>>();
+        assert!(undone.contains(&MapEvent::TilePaved(start)));
+        assert!(undone.contains(&MapEvent::BuildingBuilt(house, Building::House)));
+    }
+
+    #[test]
+    fn refused_edits_send_nothing() {
+        let mut map = Map::new(Coord2 { x: 12, y: 8 });
+        let mut history = History::new(10);
+        map.rail(Coord2 { x: 9, y: 5 }).unwrap();
+        let events = map.subscribe();
+        let (from, to) = (Coord2 { x: 6, y: 2 }, Coord2 { x: 9, y: 7 });
+        assert_eq!(
+            history.apply(&mut map, Edit::PaveAlong(from, to)),
+            Err(MapError::Occupied(Coord2 { x: 9, y: 5 }))
+        );
+        assert_eq!(events.try_iter().collect::<Vec<_>>(), vec![]);
+
+        let mut patched = map.clone();
+        patched.pave_paths(&Path::l_shaped(from, Coord2 { x: 8, y: 2 })).unwrap();
+        let patch = map.diff(&patched);
+        map.set_terrain(Coord2 { x: 8, y: 2 }, Terrain::Hills).unwrap();
+        assert!(patch.apply(&mut map).is_err());
+        assert_eq!(events.try_iter().collect::<Vec<_>>(), vec![]);
+    }
+}
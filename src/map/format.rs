@@ -0,0 +1,453 @@
+use super::*;
+use std::error::Error;
+use std::str::FromStr;
+
+/// Version written by `Map::to_text`. Bump whenever the layout of the text
+/// format changes incompatibly.
+pub const MAP_FORMAT_VERSION: u64 = 1;
+
+const MAP_FORMAT_HEADER: &str = "tilewater-map";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseMapError {
+    /// The header named a format version this build does not understand.
+    UnsupportedVersion(String),
+    /// A header record was malformed or unknown.
+    BadRecord { line: usize, record: String },
+    /// A grid character did not correspond to any tile.
+    UnknownTile { line: usize, column: usize, code: char },
+    /// A character in the grid of a layer, such as terrain or zones, did not
+    /// correspond to anything in that layer.
+    UnknownCode {
+        layer: String,
+        line: usize,
+        column: usize,
+        code: char,
+    },
+    /// A tile stands on terrain which cannot support it.
+    UnsuitableTerrain(Coord2),
+    /// The grid extends past the recorded dimensions.
+    OutOfBounds { line: usize, column: usize },
+    /// An entrance without exactly one building and one paving at its ends.
+    DanglingEntrance(Coord2),
+    /// A building that no entrance leads to.
+    BuildingWithoutEntrance(Coord2),
+    /// A building that more than one entrance leads to.
+    BuildingWithManyEntrances(Coord2),
+}
+
+impl fmt::Display for ParseMapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseMapError::UnsupportedVersion(ref version) => {
+                write!(f, "unsupported map format version '{}'", version)
+            }
+            ParseMapError::BadRecord { line, ref record } => {
+                write!(f, "line {}: malformed record '{}'", line, record)
+            }
+            ParseMapError::UnknownTile { line, column, code } => write!(
+                f,
+                "line {}, column {}: unknown tile '{}'",
+                line, column, code
+            ),
+            ParseMapError::UnknownCode {
+                ref layer,
+                line,
+                column,
+                code,
+            } => write!(
+                f,
+                "line {}, column {}: unknown {} '{}'",
+                line, column, layer, code
+            ),
+            ParseMapError::UnsuitableTerrain(location) => write!(
+                f,
+                "tile at ({}, {}) stands on unsuitable terrain",
+                location.x, location.y
+            ),
+            ParseMapError::OutOfBounds { line, column } => write!(
+                f,
+                "line {}, column {}: tile lies outside the map dimensions",
+                line, column
+            ),
+            ParseMapError::DanglingEntrance(location) => write!(
+                f,
+                "entrance at ({}, {}) does not join a building to a paving",
+                location.x, location.y
+            ),
+            ParseMapError::BuildingWithoutEntrance(location) => write!(
+                f,
+                "building at ({}, {}) has no entrance",
+                location.x, location.y
+            ),
+            ParseMapError::BuildingWithManyEntrances(location) => write!(
+                f,
+                "building at ({}, {}) has more than one entrance",
+                location.x, location.y
+            ),
+        }
+    }
+}
+
+impl Error for ParseMapError {
+    fn description(&self) -> &str {
+        "invalid map text"
+    }
+}
+
+impl Map {
+    /// Render the map in the versioned text format understood by `Map::from_str`.
+    ///
+    /// The grid section is the same as `Display` prints. Everything the grid cannot
+    /// express is written as a record above it.
+    pub fn to_text(&self) -> String {
+        let mut text = String::new();
+        text.push_str(&format!("{} {}\n", MAP_FORMAT_HEADER, MAP_FORMAT_VERSION));
+        text.push_str(&format!(
+            "dimensions {} {}\n",
+            self.dimensions.x, self.dimensions.y
+        ));
+        text.push_str(&format!("cursor {} {}\n", self.cursor.x, self.cursor.y));
+        if !self.terrain.is_empty() {
+            self.write_layer(&mut text, "terrain", |l| self.terrain(l).code());
+        }
+        if !self.zones.is_empty() {
+            self.write_layer(&mut text, "zones", |l| self.zone(l).map_or(' ', |z| z.code()));
+        }
+        let mut levelled: Vec<&BuildingTile> = self
+            .buildings
+            .values()
+            .flat_map(|origins| origins.iter().filter_map(move |o| self.building_at(*o)))
+            .filter(|building_tile| building_tile.level > 1)
+            .collect();
+        levelled.sort_by_key(|building_tile| (building_tile.origin.y, building_tile.origin.x));
+        for building_tile in levelled {
+            let origin = building_tile.origin;
+            text.push_str(&format!(
+                "level {} {} {}\n",
+                origin.x, origin.y, building_tile.level
+            ));
+        }
+        text.push_str("tiles\n");
+        text.push_str(&self.to_string());
+        text
+    }
+
+    /// Write a layer beneath the tiles as a record naming it, followed by one line
+    /// per row of the map. Blanks are left off the ends of lines.
+    fn write_layer<F>(&self, text: &mut String, layer: &str, code: F)
+    where
+        F: Fn(Coord2) -> char,
+    {
+        text.push_str(layer);
+        text.push('\n');
+        for y in 0..self.dimensions.y {
+            let row: String = (0..self.dimensions.x).map(|x| code(Coord2 { x, y })).collect();
+            text.push_str(row.trim_end());
+            text.push('\n');
+        }
+    }
+}
+
+impl FromStr for Map {
+    type Err = ParseMapError;
+
+    /// Parse either the versioned text format written by `Map::to_text`, or a bare
+    /// grid as printed by `Display`. For a bare grid the dimensions are inferred from
+    /// the widest line and the number of lines.
+    fn from_str(s: &str) -> Result<Map, ParseMapError> {
+        let lines: Vec<&str> = s.lines().collect();
+        let is_versioned = lines
+            .first()
+            .map(|l| l.split_whitespace().next() == Some(MAP_FORMAT_HEADER))
+            .unwrap_or(false);
+        if !is_versioned {
+            let dimensions = Coord2 {
+                x: lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u64,
+                y: lines.len() as u64,
+            };
+            let mut map = Map::empty(dimensions);
+            parse_grid(&mut map, &lines, 1)?;
+            relink(&mut map)?;
+            return Ok(map);
+        }
+
+        let version = lines[0][MAP_FORMAT_HEADER.len()..].trim();
+        if version.parse() != Ok(MAP_FORMAT_VERSION) {
+            return Err(ParseMapError::UnsupportedVersion(version.to_string()));
+        }
+
+        let mut dimensions = None;
+        let mut cursor = Coord2 { x: 0, y: 0 };
+        let mut levels = vec![];
+        let mut grid_start = None;
+        let mut layers = vec![];
+        let mut skip_until = 0;
+        for (index, line) in lines.iter().enumerate().skip(1) {
+            if index < skip_until {
+                continue;
+            }
+            let line_number = index + 1;
+            let bad_record = || ParseMapError::BadRecord {
+                line: line_number,
+                record: line.to_string(),
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.first() {
+                None => continue,
+                Some(&"tiles") if fields.len() == 1 => {
+                    grid_start = Some(index + 1);
+                    break;
+                }
+                Some(&"dimensions") => {
+                    let parsed = parse_coord2(&fields[1..]).ok_or_else(bad_record)?;
+                    Map::check_dimensions(parsed).map_err(|_| bad_record())?;
+                    dimensions = Some(parsed);
+                }
+                Some(&"cursor") => cursor = parse_coord2(&fields[1..]).ok_or_else(bad_record)?,
+                Some(&layer) if fields.len() == 1 && LAYERS.contains(&layer) => {
+                    let height = dimensions.ok_or_else(bad_record)?.y as usize;
+                    if index + height >= lines.len() {
+                        return Err(bad_record());
+                    }
+                    layers.push((layer, index + 1));
+                    skip_until = index + 1 + height;
+                }
+                Some(&"level") if fields.len() == 4 => {
+                    let origin = parse_coord2(&fields[1..3]).ok_or_else(bad_record)?;
+                    let level: u64 = fields[3].parse().map_err(|_| bad_record())?;
+                    if level < 1 || level > MAX_BUILDING_LEVEL {
+                        return Err(bad_record());
+                    }
+                    levels.push((line_number, origin, level));
+                }
+                Some(_) => return Err(bad_record()),
+            }
+        }
+        let dimensions = dimensions.ok_or_else(|| ParseMapError::BadRecord {
+            line: 1,
+            record: "missing dimensions".to_string(),
+        })?;
+        let grid_start = grid_start.ok_or_else(|| ParseMapError::BadRecord {
+            line: lines.len(),
+            record: "missing tiles".to_string(),
+        })?;
+
+        let mut map = Map::empty(dimensions);
+        map.cursor = cursor;
+        for (layer, start) in layers {
+            let end = start + dimensions.y as usize;
+            parse_layer(&mut map, layer, &lines[start..end], start + 1)?;
+        }
+        parse_grid(&mut map, &lines[grid_start..], grid_start + 1)?;
+        relink(&mut map)?;
+        // Levels are set once the buildings are whole again.
+        for (line, origin, level) in levels {
+            if map.building_at(origin).map(|b| b.origin) != Some(origin) {
+                return Err(ParseMapError::BadRecord {
+                    line,
+                    record: lines[line - 1].to_string(),
+                });
+            }
+            for part in map.building_tiles(origin) {
+                map.building_at_mut(part).unwrap().level = level;
+            }
+        }
+        Ok(map)
+    }
+}
+
+pub(super) fn parse_coord2(fields: &[&str]) -> Option<Coord2> {
+    if fields.len() != 2 {
+        return None;
+    }
+    let x = fields[0].parse().ok()?;
+    let y = fields[1].parse().ok()?;
+    Some(Coord2 { x, y })
+}
+
+/// The layers beneath the tiles which can be written as grids.
+const LAYERS: [&str; 2] = ["terrain", "zones"];
+
+/// Set a layer drawn in a grid, one line per row.
+fn parse_layer(
+    map: &mut Map,
+    layer: &str,
+    lines: &[&str],
+    first_line: usize,
+) -> Result<(), ParseMapError> {
+    for (y, line) in lines.iter().enumerate() {
+        for (x, code) in line.chars().enumerate() {
+            let location = Coord2 {
+                x: x as u64,
+                y: y as u64,
+            };
+            let unknown_code = || ParseMapError::UnknownCode {
+                layer: layer.to_string(),
+                line: first_line + y,
+                column: x + 1,
+                code,
+            };
+            if !map.is_bounded(location) {
+                return Err(ParseMapError::OutOfBounds {
+                    line: first_line + y,
+                    column: x + 1,
+                });
+            }
+            match layer {
+                "terrain" => {
+                    let terrain = Terrain::from_code(code).ok_or_else(unknown_code)?;
+                    map.set_terrain(location, terrain).unwrap();
+                }
+                _ if code == ' ' => {}
+                _ => {
+                    let zone = Zone::from_code(code).ok_or_else(unknown_code)?;
+                    // Checked against roads only when zoning by hand, as the tiles
+                    // are not yet in place.
+                    map.zones.insert(location, zone);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Insert the tiles drawn in a grid. Links between tiles are left as placeholders
+/// for `relink` to fill in.
+fn parse_grid(map: &mut Map, lines: &[&str], first_line: usize) -> Result<(), ParseMapError> {
+    for (y, line) in lines.iter().enumerate() {
+        for (x, code) in line.chars().enumerate() {
+            let location = Coord2 {
+                x: x as u64,
+                y: y as u64,
+            };
+            let tile = match tile_from_code(code, location) {
+                Some(Some(tile)) => tile,
+                Some(None) => continue,
+                None => {
+                    return Err(ParseMapError::UnknownTile {
+                        line: first_line + y,
+                        column: x + 1,
+                        code,
+                    })
+                }
+            };
+            if !map.is_bounded(location) {
+                return Err(ParseMapError::OutOfBounds {
+                    line: first_line + y,
+                    column: x + 1,
+                });
+            }
+            if !map.terrain(location).supports(&tile) {
+                return Err(ParseMapError::UnsuitableTerrain(location));
+            }
+            map.tiles.insert(location, tile);
+        }
+    }
+    Ok(())
+}
+
+/// The tile drawn by `code`, `Some(None)` for empty ground or `None` if unknown.
+fn tile_from_code(code: char, location: Coord2) -> Option<Option<Tile>> {
+    if let Some(class) = RoadClass::from_code(code) {
+        return Some(Some(Tile::Paving(PavingTile {
+            entryways: Neighbours::new(),
+            pavings: Neighbours::new(),
+            class,
+        })));
+    }
+    let tile = match code {
+        ' ' => return Some(None),
+        '|' | '-' => Tile::Entrance(EntranceTile {
+            orientation: if code == '|' {
+                Orientation::Vertical
+            } else {
+                Orientation::Horizontal
+            },
+            road_pos: location,
+            building_pos: location,
+        }),
+        '=' | '‖' | '+' | 'r' => Tile::Rails(RailsTile {
+            station: if code == 'r' {
+                Some(BuildingTile {
+                    building: Building::TrainStation,
+                    entryway_pos: location,
+                    origin: location,
+                    level: 1,
+                })
+            } else {
+                None
+            },
+            shape: RailShape::Straight(if code == '‖' {
+                Orientation::Vertical
+            } else {
+                Orientation::Horizontal
+            }),
+            rails: Neighbours::new(),
+        }),
+        code => Tile::Building(BuildingTile {
+            building: Building::from_code(code)?,
+            entryway_pos: location,
+            origin: location,
+            level: 1,
+        }),
+    };
+    Some(Some(tile))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_round_trips() {
+        let mut map = Map::new(Coord2 { x: 12, y: 8 });
+        for y in 0..8 {
+            map.set_terrain(Coord2 { x: 1, y }, Terrain::Water).unwrap();
+        }
+        map.set_terrain(Coord2 { x: 10, y: 1 }, Terrain::Forest)
+            .unwrap();
+        for y in 3..6 {
+            map.pave(Coord2 { x: 6, y }).unwrap();
+        }
+        map.upgrade_road(Coord2 { x: 6, y: 4 }).unwrap();
+        map.downgrade_road(Coord2 { x: 6, y: 5 }).unwrap();
+        for x in 0..12 {
+            map.rail(Coord2 { x, y: 7 }).unwrap();
+        }
+        map.build(Coord2 { x: 8, y: 2 }, Building::House).unwrap();
+        map.build(Coord2 { x: 3, y: 4 }, Building::GeneralStore)
+            .unwrap();
+        map.upgrade_building(Coord2 { x: 3, y: 4 }).unwrap();
+        map.build(Coord2 { x: 6, y: 7 }, Building::TrainStation)
+            .unwrap();
+        map.cursor = Coord2 { x: 3, y: 5 };
+
+        let text = map.to_text();
+        let parsed: Map = text.parse().unwrap();
+        assert_eq!(parsed.to_text(), text);
+        assert_eq!(parsed.get(Coord2 { x: 6, y: 2 }), map.get(Coord2 { x: 6, y: 2 }));
+        assert_eq!(parsed.get(Coord2 { x: 7, y: 2 }), map.get(Coord2 { x: 7, y: 2 }));
+        assert_eq!(parsed.get(Coord2 { x: 6, y: 4 }), map.get(Coord2 { x: 6, y: 4 }));
+        assert_eq!(parsed.buildings, map.buildings);
+        assert_eq!(
+            parsed.capacity(Coord2 { x: 4, y: 5 }),
+            Some(2 * CUSTOMERS_PER_SHOP as u64)
+        );
+        assert!(parsed.is_bridge(Coord2 { x: 1, y: 7 }));
+
+        let from_grid: Map = map.to_string().parse().unwrap();
+        assert_eq!(from_grid.to_string(), map.to_string());
+    }
+    #[test]
+    fn oversized_dimensions_are_a_bad_record() {
+        for &(x, y) in &[(::std::u64::MAX, 1), (MAX_MAP_DIMENSION + 1, 1)] {
+            let record = format!("dimensions {} {}", x, y);
+            let text = format!("{} {}\n{}\ntiles\n", MAP_FORMAT_HEADER, MAP_FORMAT_VERSION, record);
+            assert_eq!(
+                text.parse::<Map>().unwrap_err(),
+                ParseMapError::BadRecord { line: 2, record }
+            );
+        }
+    }
+}
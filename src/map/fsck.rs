@@ -0,0 +1,420 @@
+use super::*;
+
+/// A broken invariant between tiles, or between tiles and the `buildings` index,
+/// found by `Map::validate`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Inconsistency {
+    /// A paving's record of its neighbouring pavings is wrong.
+    PavingLinks(Coord2),
+    /// A paving's record of the entrances attached to it is wrong.
+    EntrywayLinks(Coord2),
+    /// An entrance does not run straight from a paving to a building entered by it.
+    DanglingEntrance(Coord2),
+    /// A building tile's origin is not the corner of a footprint covering it.
+    BuildingOrigin(Coord2),
+    /// A building tile's entryway is not an entrance into that building.
+    BuildingEntryway(Coord2),
+    /// A rails tile's record of its neighbouring rails is wrong.
+    RailsLinks(Coord2),
+    /// A building's origin is missing from the `buildings` index.
+    Unindexed(Building, Coord2),
+    /// The `buildings` index lists a building which is not there.
+    StaleIndex(Building, Coord2),
+    /// The spatial index over buildings does not match the `buildings` index.
+    StaleSpatialIndex,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Inconsistency::PavingLinks(l) => {
+                write!(
+                    f,
+                    "the paving at ({}, {}) has wrong links to pavings",
+                    l.x, l.y
+                )
+            }
+            Inconsistency::EntrywayLinks(l) => {
+                write!(
+                    f,
+                    "the paving at ({}, {}) has wrong links to entrances",
+                    l.x, l.y
+                )
+            }
+            Inconsistency::DanglingEntrance(l) => {
+                write!(
+                    f,
+                    "the entrance at ({}, {}) does not join a road to a building",
+                    l.x, l.y
+                )
+            }
+            Inconsistency::BuildingOrigin(l) => {
+                write!(f, "the building at ({}, {}) has a wrong origin", l.x, l.y)
+            }
+            Inconsistency::BuildingEntryway(l) => {
+                write!(f, "the building at ({}, {}) has a wrong entryway", l.x, l.y)
+            }
+            Inconsistency::RailsLinks(l) => {
+                write!(
+                    f,
+                    "the rails at ({}, {}) have wrong links to rails",
+                    l.x, l.y
+                )
+            }
+            Inconsistency::Unindexed(building, l) => {
+                write!(f, "the {} at ({}, {}) is not indexed", building, l.x, l.y)
+            }
+            Inconsistency::StaleIndex(building, l) => {
+                write!(
+                    f,
+                    "a {} is indexed at ({}, {}) but not there",
+                    building, l.x, l.y
+                )
+            }
+            Inconsistency::StaleSpatialIndex => {
+                write!(f, "the spatial index of buildings is out of date")
+            }
+        }
+    }
+}
+
+impl Map {
+    /// Every invariant which the links between tiles, and the `buildings` index,
+    /// break. Empty for a consistent map.
+    pub fn validate(&self) -> Vec<Inconsistency> {
+        let mut problems = vec![];
+        let mut locations: Vec<Coord2> = self.tiles.iter().map(|(l, _)| l).collect();
+        locations.sort_by_key(|l| (l.y, l.x));
+        for location in locations {
+            match *self.tiles.get(location).unwrap() {
+                Tile::Paving(ref paving_tile) => {
+                    if paving_tile.pavings != self.neighbouring_pavings(location) {
+                        problems.push(Inconsistency::PavingLinks(location));
+                    }
+                    let entryways = self.neighbouring(location, |tile| {
+                        tile.as_entrance().map(|e| e.road_pos) == Some(location)
+                    });
+                    if paving_tile.entryways != entryways {
+                        problems.push(Inconsistency::EntrywayLinks(location));
+                    }
+                }
+                Tile::Entrance(ref entrance_tile) => {
+                    if !self.entrance_is_linked(location, entrance_tile) {
+                        problems.push(Inconsistency::DanglingEntrance(location));
+                    }
+                }
+                Tile::Rails(ref rails_tile) => {
+                    if rails_tile.rails != self.neighbouring_rails(location) {
+                        problems.push(Inconsistency::RailsLinks(location));
+                    }
+                }
+                Tile::Building(_) => {}
+            }
+            if let Some(building_tile) = self.building_at(location) {
+                problems.extend(self.building_problems(location, building_tile));
+            }
+        }
+
+        let mut indexed: Vec<(Building, Coord2)> = self
+            .buildings
+            .iter()
+            .flat_map(|(&building, origins)| origins.iter().map(move |&o| (building, o)))
+            .collect();
+        indexed.sort_by_key(|&(_, o)| (o.y, o.x));
+        for (building, origin) in indexed {
+            let there = self
+                .building_at(origin)
+                .map_or(false, |b| b.building == building && b.origin == origin);
+            if !there {
+                problems.push(Inconsistency::StaleIndex(building, origin));
+            }
+        }
+        if self.spatial != SpatialIndex::of(&self.buildings) {
+            problems.push(Inconsistency::StaleSpatialIndex);
+        }
+        problems
+    }
+
+    /// Rebuild every link between tiles, and the `buildings` index, from the tiles
+    /// themselves. Entrances which lead nowhere are removed, as are all but the first
+    /// entrance of a building, and buildings left with no entrance. Returns what
+    /// `validate` found wrong beforehand.
+    pub fn repair(&mut self) -> Vec<Inconsistency> {
+        let problems = self.validate();
+        if problems.is_empty() {
+            return problems;
+        }
+        loop {
+            match relink(self) {
+                Ok(()) => break,
+                Err(ParseMapError::DanglingEntrance(location)) => {
+                    self.tiles.remove(location);
+                }
+                Err(ParseMapError::BuildingWithManyEntrances(origin)) => {
+                    for entrance in self.entrances_into(origin).into_iter().skip(1) {
+                        self.tiles.remove(entrance);
+                    }
+                }
+                Err(ParseMapError::BuildingWithoutEntrance(origin)) => {
+                    for part in self.building_tiles(origin) {
+                        match self.tiles.get_mut(part) {
+                            Some(&mut Tile::Rails(ref mut rails_tile)) => rails_tile.station = None,
+                            _ => {
+                                self.tiles.remove(part);
+                            }
+                        }
+                    }
+                }
+                Err(e) => unreachable!("relinking failed with {}", e),
+            }
+        }
+        problems
+    }
+
+    fn entrance_is_linked(&self, location: Coord2, entrance_tile: &EntranceTile) -> bool {
+        let along = |end: Coord2| {
+            Direction::between_coord2s(location, end).map(|d| d.orientation())
+                == Some(entrance_tile.orientation)
+        };
+        let road_pos = entrance_tile.road_pos;
+        let building_pos = entrance_tile.building_pos;
+        along(road_pos)
+            && along(building_pos)
+            && self.get(road_pos).and_then(Tile::as_paving).is_some()
+            && self.building_at(building_pos).map(|b| b.entryway_pos) == Some(location)
+    }
+
+    fn building_problems(
+        &self,
+        location: Coord2,
+        building_tile: &BuildingTile,
+    ) -> Vec<Inconsistency> {
+        let mut problems = vec![];
+        let BuildingTile {
+            building,
+            entryway_pos,
+            origin,
+            ..
+        } = *building_tile;
+        let origin_holds = self
+            .building_at(origin)
+            .map_or(false, |b| b.building == building && b.origin == origin);
+        if !origin_holds || !self.footprint(origin, building).contains(&location) {
+            problems.push(Inconsistency::BuildingOrigin(location));
+        }
+        let entered = self
+            .get(entryway_pos)
+            .and_then(Tile::as_entrance)
+            .and_then(|e| self.building_at(e.building_pos))
+            .map(|b| b.origin);
+        if entered != Some(origin) {
+            problems.push(Inconsistency::BuildingEntryway(location));
+        }
+        let indexed = self
+            .buildings
+            .get(&building)
+            .map_or(false, |origins| origins.contains(&location));
+        if location == origin && !indexed {
+            problems.push(Inconsistency::Unindexed(building, location));
+        }
+        problems
+    }
+
+    /// The entrances leading into the building at `origin`, in reading order.
+    fn entrances_into(&self, origin: Coord2) -> Vec<Coord2> {
+        let mut entrances: Vec<Coord2> = self
+            .building_tiles(origin)
+            .into_iter()
+            .flat_map(|part| part.neighbours())
+            .filter(|&neighbour| match self.get(neighbour) {
+                Some(&Tile::Entrance(EntranceTile { orientation, .. })) => {
+                    entrance_ends(self, neighbour, orientation)
+                        .0
+                        .iter()
+                        .any(|end| self.building_at(*end).map(|b| b.origin) == Some(origin))
+                }
+                _ => false,
+            })
+            .collect();
+        entrances.sort_by_key(|l| (l.y, l.x));
+        entrances.dedup();
+        entrances
+    }
+}
+
+/// Rebuild every derived link from the tiles alone: building footprints, entrance
+/// ends, building entryways, paving and rails adjacency, and the `buildings` index.
+///
+/// Fails on the first entrance or building which cannot be linked up, leaving the
+/// links only partly rebuilt.
+pub(super) fn relink(map: &mut Map) -> Result<(), ParseMapError> {
+    let mut locations: Vec<Coord2> = map.tiles.iter().map(|(l, _)| l).collect();
+    locations.sort_by_key(|l| (l.y, l.x));
+
+    // Buildings never touch, so each connected patch of one kind of building is a
+    // single footprint. Its first tile in reading order is its origin.
+    let mut origins = HashMap::new();
+    for &location in &locations {
+        if origins.contains_key(&location) {
+            continue;
+        }
+        let building = match map.building_at(location) {
+            Some(building_tile) => building_tile.building,
+            None => continue,
+        };
+        let mut parts = vec![location];
+        let mut i = 0;
+        while i < parts.len() {
+            for neighbour in parts[i].neighbours() {
+                let same_building =
+                    map.building_at(neighbour).map(|b| b.building) == Some(building);
+                if same_building && !parts.contains(&neighbour) {
+                    parts.push(neighbour);
+                }
+            }
+            i += 1;
+        }
+        for part in parts {
+            origins.insert(part, location);
+            map.building_at_mut(part).unwrap().origin = location;
+        }
+    }
+
+    for &location in &locations {
+        if let Some(paving_tile) = map.tiles.get_mut(location).and_then(Tile::as_paving_mut) {
+            paving_tile.entryways = Neighbours::new();
+        }
+    }
+    let mut entrances = HashMap::new();
+    for &location in &locations {
+        let orientation = match map.tiles.get(location) {
+            Some(&Tile::Entrance(EntranceTile { orientation, .. })) => orientation,
+            _ => continue,
+        };
+        let (building_ends, paving_ends) = entrance_ends(map, location, orientation);
+        if building_ends.len() != 1 || paving_ends.len() != 1 {
+            return Err(ParseMapError::DanglingEntrance(location));
+        }
+        let (building_pos, road_pos) = (building_ends[0], paving_ends[0]);
+        let origin = origins[&building_pos];
+        if entrances.insert(origin, location).is_some() {
+            return Err(ParseMapError::BuildingWithManyEntrances(origin));
+        }
+        {
+            let entrance_tile = map
+                .tiles
+                .get_mut(location)
+                .and_then(Tile::as_entrance_mut)
+                .unwrap();
+            entrance_tile.building_pos = building_pos;
+            entrance_tile.road_pos = road_pos;
+        }
+        map.tiles
+            .get_mut(road_pos)
+            .and_then(Tile::as_paving_mut)
+            .unwrap()
+            .entryways
+            .insert(Direction::between_coord2s(road_pos, location).unwrap());
+    }
+
+    map.buildings.clear();
+    for &location in &locations {
+        if let Some(&origin) = origins.get(&location) {
+            let entryway_pos = *entrances
+                .get(&origin)
+                .ok_or(ParseMapError::BuildingWithoutEntrance(origin))?;
+            let building = {
+                let building_tile = map.building_at_mut(location).unwrap();
+                building_tile.entryway_pos = entryway_pos;
+                building_tile.building
+            };
+            if location == origin {
+                map.buildings
+                    .entry(building)
+                    .or_insert_with(|| Vec::new())
+                    .push(location);
+            }
+        }
+        let neighbouring_pavings = map.neighbouring_pavings(location);
+        let neighbouring_rails = map.neighbouring_rails(location);
+        match *map.tiles.get_mut(location).unwrap() {
+            Tile::Paving(ref mut paving_tile) => paving_tile.pavings = neighbouring_pavings,
+            Tile::Rails(ref mut rails_tile) => rails_tile.rails = neighbouring_rails,
+            _ => {}
+        }
+        map.reshape_rails(location);
+    }
+    map.reindex_buildings();
+    Ok(())
+}
+
+/// The tiles at either end of an entrance at `location` which are buildings, and
+/// those which are pavings.
+fn entrance_ends(
+    map: &Map,
+    location: Coord2,
+    orientation: Orientation,
+) -> (Vec<Coord2>, Vec<Coord2>) {
+    let offset = orientation.as_offset();
+    let mut ends = vec![location + offset];
+    if location.x >= offset.x && location.y >= offset.y {
+        ends.push(location - offset);
+    }
+    let building_ends = ends
+        .iter()
+        .cloned()
+        .filter(|e| map.building_at(*e).is_some())
+        .collect();
+    let paving_ends = ends
+        .iter()
+        .cloned()
+        .filter(|e| map.tiles.get(*e).and_then(Tile::as_paving).is_some())
+        .collect();
+    (building_ends, paving_ends)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repair_rebuilds_broken_links() {
+        let mut map = Map::new(Coord2 { x: 12, y: 8 });
+        for y in 3..7 {
+            map.pave(Coord2 { x: 6, y }).unwrap();
+        }
+        map.build(Coord2 { x: 8, y: 3 }, Building::House).unwrap();
+        map.build(Coord2 { x: 3, y: 4 }, Building::GeneralStore)
+            .unwrap();
+        assert_eq!(map.validate(), vec![]);
+
+        let road_pos = Coord2 { x: 6, y: 3 };
+        map.tiles
+            .get_mut(road_pos)
+            .and_then(Tile::as_paving_mut)
+            .unwrap()
+            .entryways = Neighbours::new();
+        map.buildings.remove(&Building::GeneralStore);
+        let dangling = Coord2 { x: 7, y: 6 };
+        map.tiles.insert(
+            dangling,
+            Tile::Entrance(EntranceTile {
+                orientation: Orientation::Horizontal,
+                road_pos: Coord2 { x: 6, y: 6 },
+                building_pos: Coord2 { x: 8, y: 6 },
+            }),
+        );
+        let problems = map.validate();
+        assert!(problems.contains(&Inconsistency::EntrywayLinks(road_pos)));
+        assert!(problems.contains(&Inconsistency::DanglingEntrance(dangling)));
+        assert!(problems.contains(&Inconsistency::Unindexed(
+            Building::GeneralStore,
+            Coord2 { x: 3, y: 4 }
+        )));
+
+        assert_eq!(map.repair(), problems);
+        assert_eq!(map.validate(), vec![]);
+        assert_eq!(map.get(dangling), None);
+        map.delete(Coord2 { x: 3, y: 4 }).unwrap();
+    }
+}
@@ -0,0 +1,191 @@
+use super::*;
+use std::mem;
+
+/// Width and height of each chunk of a `Grid`, in tiles.
+pub const CHUNK_SIZE: u64 = 32;
+
+const CHUNK_AREA: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Dense storage for at most one value per location within fixed dimensions.
+///
+/// Locations are grouped into square chunks, each allocated when the first value
+/// is put in it, so that lookups are plain indexing and empty areas cost a pointer
+/// per chunk.
+#[derive(Clone, Debug)]
+pub struct Grid<T> {
+    dimensions: Coord2,
+    chunks_wide: u64,
+    chunks: Vec<Option<Vec<Option<T>>>>,
+    len: usize,
+}
+
+impl<T> Grid<T> {
+    pub fn new(dimensions: Coord2) -> Grid<T> {
+        let chunks_wide = dimensions.x.div_ceil(CHUNK_SIZE);
+        let chunks_high = dimensions.y.div_ceil(CHUNK_SIZE);
+        Grid {
+            dimensions,
+            chunks_wide,
+            chunks: (0..chunks_wide * chunks_high).map(|_| None).collect(),
+            len: 0,
+        }
+    }
+
+    pub fn dimensions(&self) -> Coord2 {
+        self.dimensions
+    }
+
+    /// How many locations hold a value.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The chunk and offset within it of `location`, if it is within the grid.
+    fn index(&self, location: Coord2) -> Option<(usize, usize)> {
+        if location.x >= self.dimensions.x || location.y >= self.dimensions.y {
+            return None;
+        }
+        let chunk = (location.y / CHUNK_SIZE) * self.chunks_wide + location.x / CHUNK_SIZE;
+        let offset = (location.y % CHUNK_SIZE) * CHUNK_SIZE + location.x % CHUNK_SIZE;
+        Some((chunk as usize, offset as usize))
+    }
+
+    pub fn get(&self, location: Coord2) -> Option<&T> {
+        let (chunk, offset) = self.index(location)?;
+        self.chunks[chunk].as_ref()?[offset].as_ref()
+    }
+
+    pub fn get_mut(&mut self, location: Coord2) -> Option<&mut T> {
+        let (chunk, offset) = self.index(location)?;
+        self.chunks[chunk].as_mut()?[offset].as_mut()
+    }
+
+    pub fn contains(&self, location: Coord2) -> bool {
+        self.get(location).is_some()
+    }
+
+    /// Put `value` at `location`, returning whatever was there before.
+    ///
+    /// Panics if `location` is outside the grid.
+    pub fn insert(&mut self, location: Coord2, value: T) -> Option<T> {
+        let (chunk, offset) = self
+            .index(location)
+            .unwrap_or_else(|| panic!("({}, {}) is outside the grid", location.x, location.y));
+        let chunk = self.chunks[chunk]
+            .get_or_insert_with(|| (0..CHUNK_AREA).map(|_| None).collect());
+        let previous = chunk[offset].replace(value);
+        if previous.is_none() {
+            self.len += 1;
+        }
+        previous
+    }
+
+    /// Take the value at `location`, if any.
+    pub fn remove(&mut self, location: Coord2) -> Option<T> {
+        let (chunk, offset) = self.index(location)?;
+        let previous = self.chunks[chunk].as_mut()?[offset].take();
+        if previous.is_some() {
+            self.len -= 1;
+        }
+        previous
+    }
+
+    /// Remove every value, keeping the dimensions.
+    pub fn clear(&mut self) {
+        for chunk in &mut self.chunks {
+            *chunk = None;
+        }
+        self.len = 0;
+    }
+
+    /// Change to `dimensions`, moving every value by `offset`. Values moved off the
+    /// grid are dropped.
+    pub fn reshape(&mut self, dimensions: Coord2, offset: (i64, i64)) {
+        let old = mem::replace(self, Grid::new(dimensions));
+        for (chunk_index, chunk) in old.chunks.into_iter().enumerate() {
+            for (index, value) in chunk.into_iter().flatten().enumerate() {
+                let value = match value {
+                    Some(value) => value,
+                    None => continue,
+                };
+                let location = chunk_location(old.chunks_wide, chunk_index as u64, index as u64);
+                let x = location.x as i64 + offset.0;
+                let y = location.y as i64 + offset.1;
+                if x >= 0 && y >= 0 {
+                    let location = Coord2 {
+                        x: x as u64,
+                        y: y as u64,
+                    };
+                    if self.index(location).is_some() {
+                        self.insert(location, value);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every location holding a value, with the value, chunk by chunk.
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item = (Coord2, &'a T)> + 'a> {
+        let chunks_wide = self.chunks_wide;
+        Box::new(
+            self.chunks
+                .iter()
+                .enumerate()
+                .filter_map(|(chunk_index, chunk)| chunk.as_ref().map(|c| (chunk_index, c)))
+                .flat_map(move |(chunk_index, chunk)| {
+                    let chunk_index = chunk_index as u64;
+                    chunk
+                        .iter()
+                        .enumerate()
+                        .filter_map(move |(offset, value)| {
+                            let offset = offset as u64;
+                            value.as_ref().map(|value| {
+                                (chunk_location(chunks_wide, chunk_index, offset), value)
+                            })
+                        })
+                }),
+        )
+    }
+}
+
+/// The location at `offset` within the chunk at `chunk_index`.
+fn chunk_location(chunks_wide: u64, chunk_index: u64, offset: u64) -> Coord2 {
+    Coord2 {
+        x: (chunk_index % chunks_wide) * CHUNK_SIZE + offset % CHUNK_SIZE,
+        y: (chunk_index / chunks_wide) * CHUNK_SIZE + offset / CHUNK_SIZE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_cross_chunk_boundaries() {
+        let mut grid = Grid::new(Coord2 { x: 70, y: 40 });
+        let locations = [
+            Coord2 { x: 0, y: 0 },
+            Coord2 { x: 31, y: 31 },
+            Coord2 { x: 32, y: 0 },
+            Coord2 { x: 69, y: 39 },
+        ];
+        for (i, &location) in locations.iter().enumerate() {
+            assert_eq!(grid.insert(location, i), None);
+        }
+        assert_eq!(grid.len(), 4);
+        assert_eq!(grid.get(Coord2 { x: 32, y: 0 }), Some(&2));
+        assert_eq!(grid.get(Coord2 { x: 70, y: 0 }), None);
+
+        let mut found: Vec<(Coord2, usize)> = grid.iter().map(|(l, v)| (l, *v)).collect();
+        found.sort_by_key(|&(_, v)| v);
+        let expected: Vec<(Coord2, usize)> = locations.iter().cloned().zip(0..).collect();
+        assert_eq!(found, expected);
+
+        assert_eq!(grid.remove(Coord2 { x: 31, y: 31 }), Some(1));
+        assert_eq!(grid.len(), 3);
+    }
+}
@@ -0,0 +1,355 @@
+use super::*;
+
+/// An edit to a map which `History` can undo and redo.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Edit {
+    Pave(Coord2),
+    Rail(Coord2),
+    /// Pave along an L-shaped path, from one location along its row then along the
+    /// column of the other.
+    PaveAlong(Coord2, Coord2),
+    /// Lay rails along an L-shaped path, as for `PaveAlong`.
+    RailAlong(Coord2, Coord2),
+    /// Build, facing a side of the building where possible as `Map::build_facing` does.
+    Build(Coord2, Building, Option<Direction>),
+    UpgradeRoad(Coord2),
+    DowngradeRoad(Coord2),
+    /// Delete whatever is at a location, cascading as `Map::delete_cascading` does.
+    Delete(Coord2),
+}
+
+impl Edit {
+    fn apply(&self, map: &mut Map) -> Result<(), MapError> {
+        match *self {
+            Edit::Pave(location) => map.pave(location),
+            Edit::Rail(location) => map.rail(location),
+            Edit::PaveAlong(from, to) => map.pave_paths(&Path::l_shaped(from, to)),
+            Edit::RailAlong(from, to) => map.rail_paths(&Path::l_shaped(from, to)),
+            Edit::Build(location, building, facing) => {
+                map.build_facing(location, building, facing)
+            }
+            Edit::UpgradeRoad(location) => map.upgrade_road(location),
+            Edit::DowngradeRoad(location) => map.downgrade_road(location),
+            Edit::Delete(location) => map.delete_cascading(location),
+        }
+    }
+}
+
+/// Everything an edit changed, so that it can be undone and redone exactly.
+#[derive(Clone, Debug)]
+struct Change {
+    edit: Edit,
+    /// Each tile the edit touched, as it was before and after.
+    tiles: Vec<(Coord2, Option<Tile>, Option<Tile>)>,
+    /// The buildings the edit put up, and those it took down.
+    built: Vec<(Building, Coord2)>,
+    demolished: Vec<(Building, Coord2)>,
+}
+
+impl Change {
+    /// The first tile the change touched which `map` no longer has as it was left,
+    /// with the change undone if `undone`, or done otherwise.
+    fn changed_since(&self, map: &Map, undone: bool) -> Option<Coord2> {
+        self.tiles
+            .iter()
+            .find(|&&(location, ref before, ref after)| {
+                let left = if undone { before } else { after };
+                map.get(location) != left.as_ref()
+            })
+            .map(|&(location, _, _)| location)
+    }
+}
+
+/// A bounded log of edits to a map, for undo and redo.
+#[derive(Clone, Debug)]
+pub struct History {
+    limit: usize,
+    /// Whether to `Map::validate` after every edit, panicking if it finds anything.
+    validate_edits: bool,
+    undo: VecDeque<Change>,
+    redo: Vec<Change>,
+}
+
+impl History {
+    /// A history remembering the last `limit` edits.
+    pub fn new(limit: usize) -> History {
+        History {
+            limit,
+            validate_edits: cfg!(debug_assertions),
+            undo: VecDeque::with_capacity(limit),
+            redo: Vec::new(),
+        }
+    }
+
+    /// Check the map after every edit. On by default in debug builds only.
+    pub fn validate_edits(&mut self, validate: bool) {
+        self.validate_edits = validate;
+    }
+
+    /// Forget every edit, as when the map is resized under them.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Make `edit` to `map`, remembering how to undo it. An edit which fails is
+    /// rolled back entirely, and not remembered.
+    pub fn apply(&mut self, map: &mut Map, edit: Edit) -> Result<(), MapError> {
+        map.begin_journal();
+        let result = edit.apply(map);
+        let mut journal = map.end_journal();
+        if let Err(e) = result {
+            map.roll_back(journal);
+            return Err(e);
+        }
+        map.commit(&mut journal);
+
+        if self.validate_edits {
+            let problems = map.validate();
+            assert!(
+                problems.is_empty(),
+                "{:?} left the map inconsistent: {:?}",
+                edit,
+                problems
+            );
+        }
+
+        let (built, demolished) = journal.built_and_demolished();
+        let tiles = journal
+            .tiles
+            .into_iter()
+            .map(|(location, before)| {
+                let after = map.get(location).cloned();
+                (location, before, after)
+            })
+            .collect();
+        self.undo.push_back(Change {
+            edit,
+            tiles,
+            built,
+            demolished,
+        });
+        if self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+        self.redo.clear();
+        Ok(())
+    }
+
+    /// Undo the most recent edit, returning it. Buildings put up since, outside
+    /// this history, are left standing, and an edit whose tiles have changed since
+    /// is refused with `MapError::ChangedSince`.
+    pub fn undo(&mut self, map: &mut Map) -> Result<Option<Edit>, MapError> {
+        let change = match self.undo.pop_back() {
+            Some(change) => change,
+            None => return Ok(None),
+        };
+        if let Some(location) = change.changed_since(map, false) {
+            self.undo.push_back(change);
+            return Err(MapError::ChangedSince(location));
+        }
+        map.restore(
+            change
+                .tiles
+                .iter()
+                .rev()
+                .map(|&(location, ref before, _)| (location, before.clone())),
+        );
+        for &(building, origin) in &change.built {
+            map.unindex_building(building, origin);
+        }
+        for &(building, origin) in &change.demolished {
+            map.index_building(building, origin);
+        }
+        let edit = change.edit;
+        self.redo.push(change);
+        Ok(Some(edit))
+    }
+
+    /// Redo the most recently undone edit, returning it, as `undo` does.
+    pub fn redo(&mut self, map: &mut Map) -> Result<Option<Edit>, MapError> {
+        let change = match self.redo.pop() {
+            Some(change) => change,
+            None => return Ok(None),
+        };
+        if let Some(location) = change.changed_since(map, true) {
+            self.redo.push(change);
+            return Err(MapError::ChangedSince(location));
+        }
+        map.restore(
+            change
+                .tiles
+                .iter()
+                .map(|&(location, _, ref after)| (location, after.clone())),
+        );
+        for &(building, origin) in &change.demolished {
+            map.unindex_building(building, origin);
+        }
+        for &(building, origin) in &change.built {
+            map.index_building(building, origin);
+        }
+        let edit = change.edit;
+        self.undo.push_back(change);
+        Ok(Some(edit))
+    }
+}
+
+/// What an edit has changed so far, while it is being made.
+#[derive(Clone, Debug, Default)]
+pub(super) struct Journal {
+    /// Each tile overwritten, as it was beforehand.
+    pub tiles: Vec<(Coord2, Option<Tile>)>,
+    /// The locations in `tiles`.
+    pub recorded: HashSet<Coord2>,
+    /// Every change to the index of buildings, in order.
+    pub buildings: Vec<IndexChange>,
+    /// The events emitted, held back until the edit is committed.
+    pub events: Vec<MapEvent>,
+}
+
+/// A building added to or removed from the index of buildings.
+#[derive(Copy, Clone, Debug)]
+pub(super) enum IndexChange {
+    Indexed(Building, Coord2),
+    /// Removed from this position among the origins of its kind.
+    Unindexed(Building, Coord2, usize),
+}
+
+impl Journal {
+    /// The buildings indexed since the journal began which still are, and those
+    /// unindexed which were indexed before it began.
+    fn built_and_demolished(&self) -> (Vec<(Building, Coord2)>, Vec<(Building, Coord2)>) {
+        let (mut built, mut demolished) = (vec![], vec![]);
+        for change in &self.buildings {
+            let (added, removed, entry) = match *change {
+                IndexChange::Indexed(building, origin) => {
+                    (&mut built, &mut demolished, (building, origin))
+                }
+                IndexChange::Unindexed(building, origin, _) => {
+                    (&mut demolished, &mut built, (building, origin))
+                }
+            };
+            match removed.iter().position(|e| *e == entry) {
+                Some(index) => {
+                    removed.remove(index);
+                }
+                None => added.push(entry),
+            }
+        }
+        (built, demolished)
+    }
+}
+
+impl Map {
+    /// Send the events held in `journal` now that its edit has gone through.
+    pub(super) fn commit(&mut self, journal: &mut Journal) {
+        for event in journal.events.drain(..) {
+            self.emit(event);
+        }
+    }
+
+    /// Undo everything in `journal`, leaving the tiles and the order of the index
+    /// of buildings exactly as they were when it began. Its events are dropped, and
+    /// none are emitted for the undoing, since subscribers never saw the edit.
+    pub(super) fn roll_back(&mut self, journal: Journal) {
+        for (location, tile) in journal.tiles.into_iter().rev() {
+            match tile {
+                Some(tile) => self.insert_tile(location, tile),
+                None => self.remove_tile(location),
+            }
+        }
+        for change in journal.buildings.into_iter().rev() {
+            match change {
+                IndexChange::Indexed(building, origin) => {
+                    self.unindex_building(building, origin);
+                }
+                IndexChange::Unindexed(building, origin, index) => {
+                    self.buildings
+                        .entry(building)
+                        .or_insert_with(Vec::new)
+                        .insert(index, origin);
+                    self.spatial.insert(building, origin);
+                }
+            }
+        }
+    }
+
+    /// Put tiles back exactly as given, bypassing every rule and link update, but
+    /// still emitting a `MapEvent` for each change.
+    pub(super) fn restore<I>(&mut self, tiles: I)
+    where
+        I: Iterator<Item = (Coord2, Option<Tile>)>,
+    {
+        for (location, tile) in tiles {
+            let before = self.get(location).cloned();
+            match tile {
+                Some(tile) => self.insert_tile(location, tile),
+                None => self.remove_tile(location),
+            }
+            self.emit_changed(location, before.as_ref());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_restores_cascaded_delete() {
+        let house = Coord2 { x: 12, y: 4 };
+        let mut map = map_with_street(&[(house, Building::House)]);
+        let mut history = History::new(10);
+        let before = map.to_text();
+        let buildings_before = map.buildings.clone();
+
+        history.apply(&mut map, Edit::Delete(Coord2 { x: 10, y: 4 })).unwrap();
+        assert!(map.get(house).is_none());
+
+        assert_eq!(
+            history.undo(&mut map),
+            Ok(Some(Edit::Delete(Coord2 { x: 10, y: 4 })))
+        );
+        assert_eq!(map.to_text(), before);
+        assert_eq!(map.buildings, buildings_before);
+
+        history.redo(&mut map).unwrap();
+        assert!(map.get(house).is_none());
+    }
+
+    #[test]
+    fn undo_leaves_buildings_from_outside_the_history_standing() {
+        let mut map = map_with_street(&[]);
+        let mut history = History::new(10);
+        let end = Coord2 { x: 10, y: 10 };
+        history.apply(&mut map, Edit::Pave(end)).unwrap();
+        // As growth builds, straight onto the map.
+        let house = Coord2 { x: 12, y: 4 };
+        map.build(house, Building::House).unwrap();
+
+        assert_eq!(history.undo(&mut map), Ok(Some(Edit::Pave(end))));
+        assert_eq!(map.buildings[&Building::House], vec![house]);
+
+        history.apply(&mut map, Edit::Delete(house)).unwrap();
+        map.build(house, Building::House).unwrap();
+        assert!(history.undo(&mut map).is_err());
+        assert_eq!(map.buildings[&Building::House], vec![house]);
+    }
+
+    #[test]
+    fn rolling_back_leaves_the_buildings_in_order() {
+        let (first, second) = (Coord2 { x: 12, y: 4 }, Coord2 { x: 12, y: 8 });
+        let mut map = map_with_street(&[(first, Building::House), (second, Building::House)]);
+
+        map.begin_journal();
+        map.delete_cascading(first).unwrap();
+        map.build(first, Building::House).unwrap();
+        let journal = map.end_journal();
+        assert_eq!(map.buildings[&Building::House], vec![second, first]);
+        assert_eq!(journal.built_and_demolished(), (vec![], vec![]));
+
+        map.roll_back(journal);
+        assert_eq!(map.buildings[&Building::House], vec![first, second]);
+        assert!(map.validate().is_empty());
+    }
+}
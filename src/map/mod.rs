@@ -0,0 +1,1360 @@
+use super::*;
+use std::collections::*;
+
+mod error;
+mod events;
+mod format;
+mod fsck;
+mod grid;
+mod history;
+mod patch;
+mod resize;
+mod spatial;
+
+pub use self::error::*;
+pub use self::events::*;
+pub use self::format::*;
+pub use self::fsck::*;
+pub use self::grid::*;
+pub use self::history::*;
+pub use self::patch::*;
+use self::spatial::SpatialIndex;
+
+/// How far in a straight line a zoned tile may be from a road.
+pub const ZONE_REACH: u64 = 4;
+
+/// The widest or tallest a map may be, keeping its grids to a sensible size.
+pub const MAX_MAP_DIMENSION: u64 = 4096;
+
+#[derive(Clone, Debug)]
+pub struct Map {
+    pub cursor: Coord2,
+    dimensions: Coord2,
+    tiles: Grid<Tile>,
+    /// The ground under the tiles, holding only what is not grass.
+    terrain: Grid<Terrain>,
+    zones: Grid<Zone>,
+    /// How desirable each tile is, as of the last `survey_land_value`.
+    land_value: LandValue,
+    /// The origin of every building, by kind. Kept in order, so that buildings are
+    /// always visited in the same order.
+    pub buildings: BTreeMap<Building, Vec<Coord2>>,
+    /// The same origins as `buildings`, bucketed by where they are.
+    spatial: SpatialIndex,
+    /// While an edit is being recorded, what it has changed so far.
+    journal: Option<history::Journal>,
+    subscribers: Subscribers,
+}
+
+impl Map {
+    pub fn new(dimensions: Coord2) -> Map {
+        let mut map = Map::empty(dimensions);
+        // The paving sits two rows down, leaving the top row for a railway and the
+        // next for the entrance of a station on it.
+        let location = Coord2 {
+            x: dimensions.x / 2,
+            y: 2,
+        };
+        if map.is_bounded(location) {
+            map.tiles.insert(
+                location,
+                Tile::Paving(PavingTile {
+                    entryways: Neighbours::new(),
+                    pavings: Neighbours::new(),
+                    class: RoadClass::default(),
+                }),
+            );
+        }
+        map
+    }
+
+    /// A map with no tiles at all, not even the starting paving.
+    /// Refuse dimensions wider or taller than `MAX_MAP_DIMENSION`, before a map is
+    /// made from them.
+    pub fn check_dimensions(dimensions: Coord2) -> Result<(), MapError> {
+        if dimensions.x > MAX_MAP_DIMENSION || dimensions.y > MAX_MAP_DIMENSION {
+            return Err(MapError::TooLarge(dimensions));
+        }
+        Ok(())
+    }
+
+    pub fn empty(dimensions: Coord2) -> Map {
+        Map {
+            cursor: Coord2 { x: 0, y: 0 },
+            dimensions,
+            tiles: Grid::new(dimensions),
+            terrain: Grid::new(dimensions),
+            zones: Grid::new(dimensions),
+            land_value: LandValue::new(dimensions),
+            buildings: BTreeMap::new(),
+            spatial: SpatialIndex::default(),
+            journal: None,
+            subscribers: Subscribers::default(),
+        }
+    }
+
+    pub fn height(&self) -> u64 {
+        self.dimensions.y
+    }
+
+    pub fn width(&self) -> u64 {
+        self.dimensions.x
+    }
+
+    /// Build `building` with the north-west corner of its footprint at `location`,
+    /// with an entrance from a road two tiles away from one of its edges. A
+    /// `Building::TrainStation` is instead built onto existing rails.
+    pub fn build(&mut self, location: Coord2, building: Building) -> Result<(), MapError> {
+        self.build_facing(location, building, None)
+    }
+
+    /// Build as `build` does, with the entrance on the `facing` side of the building
+    /// where a road there allows it.
+    pub fn build_facing(
+        &mut self,
+        location: Coord2,
+        building: Building,
+        facing: Option<Direction>,
+    ) -> Result<(), MapError> {
+        self.check_build(location, building)?;
+        let entryways = self.entryways_to_build_from(location, building);
+        let entryway = *facing
+            .and_then(|facing| entryways.iter().find(|e| e.2 == facing))
+            .unwrap_or(&entryways[0]);
+        self.build_through(location, building, entryway)
+    }
+
+    /// Build as `build` does, with the entrance at `entrance`, which must be one of
+    /// the `entrances` for the building there.
+    pub fn build_at_entrance(
+        &mut self,
+        location: Coord2,
+        building: Building,
+        entrance: Coord2,
+    ) -> Result<(), MapError> {
+        self.check_build(location, building)?;
+        let entryway = self
+            .entryways_to_build_from(location, building)
+            .into_iter()
+            .find(|e| e.1 == entrance)
+            .ok_or(MapError::NoAdjacentRoad(entrance))?;
+        self.build_through(location, building, entryway)
+    }
+
+    /// Build once `check_build` has passed, through an entryway given by
+    /// `entryways_to_build_from`.
+    fn build_through(
+        &mut self,
+        location: Coord2,
+        building: Building,
+        (road_pos, entryway_pos, facing): (Coord2, Coord2, Direction),
+    ) -> Result<(), MapError> {
+        let orientation = facing.orientation();
+        let side = Direction::between_coord2s(road_pos, entryway_pos)
+            .ok_or(MapError::InconsistentAdjacency(road_pos))?;
+        let door_pos = entryway_pos
+            .step(side)
+            .ok_or(MapError::InconsistentAdjacency(entryway_pos))?;
+
+        // Insert the new building across its footprint, or mark the rails as a station.
+        let building_tile = BuildingTile {
+            building: building,
+            entryway_pos: entryway_pos,
+            origin: location,
+            level: 1,
+        };
+        for part in self.footprint(location, building) {
+            if building == Building::TrainStation {
+                self.tile_mut(part)
+                    .and_then(Tile::as_rails_mut)
+                    .ok_or(MapError::NotOnRails(part))?
+                    .station = Some(building_tile.clone());
+            } else {
+                self.insert_tile(part, Tile::Building(building_tile.clone()));
+            }
+        }
+        self.index_building(building, location);
+
+        // Insert the new entrance.
+        let entrance_tile = Tile::Entrance(EntranceTile {
+            road_pos: road_pos,
+            building_pos: door_pos,
+            orientation: orientation,
+        });
+        self.insert_tile(entryway_pos, entrance_tile);
+
+        // Update road the entrance attaches, to record this new entryway.
+        self.tile_mut(road_pos)
+            .and_then(Tile::as_paving_mut)
+            .ok_or(MapError::InconsistentAdjacency(road_pos))?
+            .entryways
+            .insert(side);
+
+        self.emit(MapEvent::BuildingBuilt(location, building));
+        Ok(())
+    }
+
+    pub fn pave(&mut self, location: Coord2) -> Result<(), MapError> {
+        self.check_pave(location)?;
+        let neighbouring_pavings = self.neighbouring_pavings(location);
+        for side in neighbouring_pavings.directions() {
+            let neighbouring_paving = location.step(side).unwrap();
+            self.tile_mut(neighbouring_paving)
+                .and_then(Tile::as_paving_mut)
+                .ok_or(MapError::InconsistentAdjacency(neighbouring_paving))?
+                .pavings
+                .insert(side.opposite());
+        }
+        let paving_tile = Tile::Paving(PavingTile {
+            entryways: Neighbours::new(),
+            pavings: neighbouring_pavings,
+            class: RoadClass::default(),
+        });
+        self.insert_tile(location, paving_tile);
+        self.emit(MapEvent::TilePaved(location));
+        Ok(())
+    }
+
+    /// Pave along `path`, as `pave_paths` does.
+    pub fn pave_path(&mut self, path: Path) -> Result<(), MapError> {
+        self.pave_paths(&[path])
+    }
+
+    /// Pave every tile along `paths` which is not paved already. Every tile is
+    /// checked first, so that either all of them are paved or none are.
+    pub fn pave_paths(&mut self, paths: &[Path]) -> Result<(), MapError> {
+        let locations = self.unlaid(paths, |tile| tile.as_paving().is_some());
+        for &location in &locations {
+            self.check_pave(location)?;
+        }
+        for location in locations {
+            self.pave(location)?;
+        }
+        Ok(())
+    }
+
+    /// Lay rails along `path`, as `rail_paths` does.
+    pub fn rail_path(&mut self, path: Path) -> Result<(), MapError> {
+        self.rail_paths(&[path])
+    }
+
+    /// Lay rails on every tile along `paths` which has none already. Every tile is
+    /// checked first, so that either all of them get rails or none do.
+    pub fn rail_paths(&mut self, paths: &[Path]) -> Result<(), MapError> {
+        let locations = self.unlaid(paths, |tile| tile.as_rails().is_some());
+        for &location in &locations {
+            self.check_rail(location)?;
+        }
+        for location in locations {
+            self.rail(location)?;
+        }
+        Ok(())
+    }
+
+    /// Each location along `paths` once, in order, except those already `laid`.
+    fn unlaid<F>(&self, paths: &[Path], laid: F) -> Vec<Coord2>
+    where
+        F: Fn(&Tile) -> bool,
+    {
+        let mut locations = vec![];
+        for path in paths {
+            for location in *path {
+                if !locations.contains(&location) && !self.get(location).map_or(false, &laid) {
+                    locations.push(location);
+                }
+            }
+        }
+        locations
+    }
+
+    /// Raise the paving at `location` to the next road class.
+    pub fn upgrade_road(&mut self, location: Coord2) -> Result<(), MapError> {
+        self.change_road_class(location, RoadClass::upgraded)
+    }
+
+    /// Lower the paving at `location` to the previous road class.
+    pub fn downgrade_road(&mut self, location: Coord2) -> Result<(), MapError> {
+        self.change_road_class(location, RoadClass::downgraded)
+    }
+
+    /// Raise or lower the paving at `location` a class at a time until it is `class`.
+    pub(crate) fn set_road_class(
+        &mut self,
+        location: Coord2,
+        class: RoadClass,
+    ) -> Result<(), MapError> {
+        loop {
+            let current = self
+                .get(location)
+                .and_then(Tile::as_paving)
+                .ok_or(MapError::NotPaved(location))?
+                .class;
+            if current == class {
+                return Ok(());
+            } else if class.cost() < current.cost() {
+                self.upgrade_road(location)?;
+            } else {
+                self.downgrade_road(location)?;
+            }
+        }
+    }
+
+    fn change_road_class<F>(&mut self, location: Coord2, change: F) -> Result<(), MapError>
+    where
+        F: Fn(&RoadClass) -> Option<RoadClass>,
+    {
+        self.check_bounded(location)?;
+        let class = self
+            .get(location)
+            .and_then(Tile::as_paving)
+            .ok_or(MapError::NotPaved(location))?
+            .class;
+        let changed = change(&class).ok_or(MapError::NoFurtherRoadClass(location, class))?;
+        self.tile_mut(location)
+            .and_then(Tile::as_paving_mut)
+            .ok_or(MapError::NotPaved(location))?
+            .class = changed;
+        self.emit(MapEvent::RoadClassChanged(location, changed));
+        Ok(())
+    }
+
+    /// Raise the building whose footprint starts at `origin` to the next level.
+    pub fn upgrade_building(&mut self, origin: Coord2) -> Result<(), MapError> {
+        self.change_level(origin, |level| Some(level + 1).filter(|l| *l <= MAX_BUILDING_LEVEL))
+    }
+
+    /// Lower the building whose footprint starts at `origin` to the previous level.
+    pub fn downgrade_building(&mut self, origin: Coord2) -> Result<(), MapError> {
+        self.change_level(origin, |level| level.checked_sub(1).filter(|l| *l >= 1))
+    }
+
+    fn change_level<F>(&mut self, origin: Coord2, change: F) -> Result<(), MapError>
+    where
+        F: Fn(u64) -> Option<u64>,
+    {
+        let (building, level) = self
+            .building_at(origin)
+            .filter(|building_tile| building_tile.origin == origin)
+            .map(|building_tile| (building_tile.building, building_tile.level))
+            .ok_or(MapError::NoBuilding(origin))?;
+        let changed = change(level).ok_or(MapError::NoFurtherLevel(origin, level))?;
+        for part in self.building_tiles(origin) {
+            self.building_at_mut(part)
+                .ok_or(MapError::InconsistentAdjacency(part))?
+                .level = changed;
+        }
+        self.emit(MapEvent::LevelChanged(origin, building, changed));
+        Ok(())
+    }
+
+    pub fn rail(&mut self, location: Coord2) -> Result<(), MapError> {
+        self.check_rail(location)?;
+        let neighbouring_rails = self.neighbouring_rails(location);
+        let rail_tile = Tile::Rails(RailsTile {
+            rails: neighbouring_rails,
+            shape: RailShape::Straight(Orientation::Horizontal),
+            station: None,
+        });
+        self.insert_tile(location, rail_tile);
+        self.reshape_rails(location);
+        for side in neighbouring_rails.directions() {
+            let neighbouring_rail = location.step(side).unwrap();
+            self.tile_mut(neighbouring_rail)
+                .and_then(Tile::as_rails_mut)
+                .ok_or(MapError::InconsistentAdjacency(neighbouring_rail))?
+                .rails
+                .insert(side.opposite());
+            self.reshape_rails(neighbouring_rail);
+        }
+        self.emit(MapEvent::RailLaid(location));
+        Ok(())
+    }
+
+    /// Recompute the shape of the rails at `location` from the rails it joins.
+    fn reshape_rails(&mut self, location: Coord2) {
+        if let Some(rails_tile) = self.tile_mut(location).and_then(Tile::as_rails_mut) {
+            let connections = rails_tile.rails.directions();
+            let lone = match rails_tile.shape {
+                RailShape::Straight(orientation) => orientation,
+                _ => Orientation::Horizontal,
+            };
+            rails_tile.shape = RailShape::from_connections(&connections, lone);
+        }
+    }
+
+    /// Remove whatever is at `location`. Deleting a building or its entrance removes
+    /// both. Pavings which buildings are entered from are refused with
+    /// `WouldOrphanBuilding`; use `delete_cascading` to remove those buildings too.
+    pub fn delete(&mut self, location: Coord2) -> Result<(), MapError> {
+        if let Some(&Tile::Paving(PavingTile { entryways, .. })) = self.get(location) {
+            if let Some(&entryway_pos) = entryways.locations(location).first() {
+                let building_pos = self
+                    .get(entryway_pos)
+                    .and_then(Tile::as_entrance)
+                    .ok_or(MapError::InconsistentAdjacency(entryway_pos))?
+                    .building_pos;
+                let origin = self
+                    .building_at(building_pos)
+                    .ok_or(MapError::InconsistentAdjacency(building_pos))?
+                    .origin;
+                return Err(MapError::WouldOrphanBuilding(origin));
+            }
+        }
+        self.delete_cascading(location)
+    }
+
+    /// Remove whatever is at `location`, along with every building entered from it.
+    pub fn delete_cascading(&mut self, location: Coord2) -> Result<(), MapError> {
+        let tile_cloned = match self.get(location) {
+            Some(tile) => tile.clone(),
+            None => return Ok(()),
+        };
+        match tile_cloned {
+            Tile::Building(BuildingTile { origin, .. }) => {
+                self.delete_building(origin)?;
+            }
+            Tile::Entrance(EntranceTile { building_pos, .. }) => {
+                let origin = self
+                    .building_at(building_pos)
+                    .ok_or(MapError::InconsistentAdjacency(building_pos))?
+                    .origin;
+                self.delete_building(origin)?;
+            }
+            Tile::Paving(PavingTile {
+                entryways, pavings, ..
+            }) => {
+                // Delete entrances, their buildings and this paving.
+                for entryway_pos in entryways.locations(location) {
+                    self.delete_cascading(entryway_pos)?;
+                }
+                // Remove record from neighbouring paved tiles.
+                for side in pavings.directions() {
+                    let paving_pos = location
+                        .step(side)
+                        .ok_or(MapError::InconsistentAdjacency(location))?;
+                    self.tile_mut(paving_pos)
+                        .and_then(Tile::as_paving_mut)
+                        .ok_or(MapError::InconsistentAdjacency(paving_pos))?
+                        .pavings
+                        .remove(side.opposite());
+                }
+                // Remove paving.
+                self.remove_tile(location);
+                self.emit(MapEvent::PavingRemoved(location));
+            }
+            Tile::Rails(RailsTile {
+                station: Some(BuildingTile { origin, .. }),
+                ..
+            }) => {
+                // Only remove the station, leaving the rails for a second delete.
+                self.delete_building(origin)?;
+            }
+            Tile::Rails(RailsTile { rails, .. }) => {
+                // Remove record from neighbouring rails, which may change shape.
+                for side in rails.directions() {
+                    let rail_pos = location
+                        .step(side)
+                        .ok_or(MapError::InconsistentAdjacency(location))?;
+                    self.tile_mut(rail_pos)
+                        .and_then(Tile::as_rails_mut)
+                        .ok_or(MapError::InconsistentAdjacency(rail_pos))?
+                        .rails
+                        .remove(side.opposite());
+                    self.reshape_rails(rail_pos);
+                }
+                // Remove rails.
+                self.remove_tile(location);
+                self.emit(MapEvent::RailsRemoved(location));
+            }
+        }
+        Ok(())
+    }
+
+    /// Record `building` at `origin` in `buildings` and the spatial index.
+    fn index_building(&mut self, building: Building, origin: Coord2) {
+        self.buildings
+            .entry(building)
+            .or_insert_with(|| Vec::new())
+            .push(origin);
+        self.spatial.insert(building, origin);
+        if let Some(ref mut journal) = self.journal {
+            journal.buildings.push(IndexChange::Indexed(building, origin));
+        }
+    }
+
+    /// Forget `building` at `origin`, returning whether it was indexed at all.
+    fn unindex_building(&mut self, building: Building, origin: Coord2) -> bool {
+        let index = self
+            .buildings
+            .get(&building)
+            .and_then(|origins| origins.iter().position(|o| *o == origin));
+        match index {
+            Some(index) => {
+                self.buildings.get_mut(&building).unwrap().remove(index);
+                self.spatial.remove(building, origin);
+                if let Some(ref mut journal) = self.journal {
+                    journal
+                        .buildings
+                        .push(IndexChange::Unindexed(building, origin, index));
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the building whose footprint starts at `origin`, and its entrance.
+    fn delete_building(&mut self, origin: Coord2) -> Result<(), MapError> {
+        let (building, entryway_pos) = match self.building_at(origin) {
+            Some(&BuildingTile {
+                building,
+                entryway_pos,
+                ..
+            }) => (building, entryway_pos),
+            None => return Err(MapError::InconsistentAdjacency(origin)),
+        };
+
+        // Remove record of building entryway from its access paving.
+        let paving_pos = match self.tiles.get(entryway_pos) {
+            Some(&Tile::Entrance(EntranceTile { road_pos, .. })) => road_pos,
+            _ => return Err(MapError::InconsistentAdjacency(entryway_pos)),
+        };
+        let side = Direction::between_coord2s(paving_pos, entryway_pos)
+            .ok_or(MapError::InconsistentAdjacency(entryway_pos))?;
+        match self.tile_mut(paving_pos) {
+            Some(&mut Tile::Paving(PavingTile {
+                ref mut entryways, ..
+            })) => {
+                entryways.remove(side);
+            }
+            _ => return Err(MapError::InconsistentAdjacency(paving_pos)),
+        }
+
+        // Remove record of building.
+        if !self.unindex_building(building, origin) {
+            return Err(MapError::InconsistentAdjacency(origin));
+        }
+
+        // Remove building and entryway tiles. A station's rails stay behind.
+        for part in self.building_tiles(origin) {
+            if building == Building::TrainStation {
+                self.tile_mut(part)
+                    .and_then(Tile::as_rails_mut)
+                    .ok_or(MapError::InconsistentAdjacency(part))?
+                    .station = None;
+            } else {
+                self.remove_tile(part);
+            }
+        }
+        self.remove_tile(entryway_pos);
+        self.emit(MapEvent::BuildingDeleted(origin, building));
+        Ok(())
+    }
+
+    /// Move the cursor one tile, keeping it on the map.
+    pub fn move_cursor(&mut self, direction: Direction) -> Result<(), MapError> {
+        let (dx, dy) = direction.as_offset();
+        if (self.cursor.x == 0 && dx < 0) || (self.cursor.y == 0 && dy < 0) {
+            return Err(MapError::OutOfBounds(self.cursor));
+        }
+        let location = Coord2 {
+            x: (self.cursor.x as i64 + dx) as u64,
+            y: (self.cursor.y as i64 + dy) as u64,
+        };
+        self.check_bounded(location)?;
+        self.cursor = location;
+        Ok(())
+    }
+
+    pub fn get(&self, location: Coord2) -> Option<&Tile> {
+        self.tiles.get(location)
+    }
+
+    /// Every location holding a tile, with the tile.
+    pub fn tiles<'a>(&'a self) -> Box<Iterator<Item = (Coord2, &'a Tile)> + 'a> {
+        self.tiles.iter()
+    }
+
+    pub fn terrain(&self, location: Coord2) -> Terrain {
+        self.terrain.get(location).cloned().unwrap_or_default()
+    }
+
+    /// Change the ground at `location`, so long as it still supports any tile there.
+    pub fn set_terrain(&mut self, location: Coord2, terrain: Terrain) -> Result<(), MapError> {
+        self.check_bounded(location)?;
+        if let Some(tile) = self.get(location) {
+            if !terrain.supports(tile) {
+                return Err(MapError::UnsuitableTerrain(location, terrain));
+            }
+        }
+        if terrain == Terrain::Grass {
+            self.terrain.remove(location);
+        } else {
+            self.terrain.insert(location, terrain);
+        }
+        Ok(())
+    }
+
+    pub fn zone(&self, location: Coord2) -> Option<Zone> {
+        self.zones.get(location).cloned()
+    }
+
+    /// Every location zoned as `zone`.
+    pub fn zoned(&self, zone: Zone) -> Vec<Coord2> {
+        self.zones
+            .iter()
+            .filter(|&(_, z)| *z == zone)
+            .map(|(location, _)| location)
+            .collect()
+    }
+
+    /// Zone `location`, or clear its zone with `None`. Zones go on empty, buildable
+    /// ground near a road, and stay beneath whatever grows there.
+    pub fn set_zone(&mut self, location: Coord2, zone: Option<Zone>) -> Result<(), MapError> {
+        match zone {
+            Some(zone) => {
+                self.check_zone(location)?;
+                self.zones.insert(location, zone);
+            }
+            None => {
+                self.check_bounded(location)?;
+                self.zones.remove(location);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn can_zone(&self, location: Coord2) -> bool {
+        self.check_zone(location).is_ok()
+    }
+
+    pub fn check_zone(&self, location: Coord2) -> Result<(), MapError> {
+        self.check_bounded(location)?;
+        if self.get(location).is_some() {
+            return Err(MapError::Occupied(location));
+        }
+        self.check_terrain(location, Terrain::is_buildable)?;
+        if self.nearest_road(location).is_none() {
+            return Err(MapError::NoNearbyRoad(location));
+        }
+        Ok(())
+    }
+
+    /// The closest paving within `ZONE_REACH` of `location` in a straight line, and
+    /// how many steps away it is.
+    pub fn nearest_road(&self, location: Coord2) -> Option<(Coord2, u64)> {
+        Direction::all()
+            .iter()
+            .filter_map(|&direction| {
+                let mut reached = location;
+                for steps in 1..ZONE_REACH + 1 {
+                    reached = reached.step(direction)?;
+                    if self.get(reached).and_then(Tile::as_paving).is_some() {
+                        return Some((reached, steps));
+                    }
+                }
+                None
+            })
+            .min_by_key(|&(_, steps)| steps)
+    }
+
+    /// How desirable `location` is to live on, from 0 to 1.
+    pub fn land_value(&self, location: Coord2) -> f64 {
+        self.land_value.get(location)
+    }
+
+    /// Work out land values afresh from the tiles as they are now.
+    pub fn survey_land_value(&mut self) {
+        self.land_value = LandValue::survey(self);
+    }
+
+    /// Whether a road or rails at `location` cross water.
+    pub fn is_bridge(&self, location: Coord2) -> bool {
+        match self.get(location) {
+            Some(&Tile::Paving(_)) | Some(&Tile::Rails(_)) => {
+                self.terrain(location) == Terrain::Water
+            }
+            _ => false,
+        }
+    }
+
+    fn check_terrain<F>(&self, location: Coord2, allowed: F) -> Result<(), MapError>
+    where
+        F: Fn(&Terrain) -> bool,
+    {
+        let terrain = self.terrain(location);
+        if allowed(&terrain) {
+            Ok(())
+        } else {
+            Err(MapError::UnsuitableTerrain(location, terrain))
+        }
+    }
+
+    /// The part of a building at `location`, including a station laid over rails.
+    pub fn building_at(&self, location: Coord2) -> Option<&BuildingTile> {
+        match self.get(location) {
+            Some(&Tile::Building(ref building_tile)) => Some(building_tile),
+            Some(&Tile::Rails(RailsTile {
+                station: Some(ref building_tile),
+                ..
+            })) => Some(building_tile),
+            _ => None,
+        }
+    }
+
+    /// How many residents the building at `location` has places for at its level.
+    /// `None` where there is no building, or no limit.
+    pub fn capacity(&self, location: Coord2) -> Option<u64> {
+        self.building_at(location).and_then(BuildingTile::capacity)
+    }
+
+    fn building_at_mut(&mut self, location: Coord2) -> Option<&mut BuildingTile> {
+        match self.tile_mut(location) {
+            Some(&mut Tile::Building(ref mut building_tile)) => Some(building_tile),
+            Some(&mut Tile::Rails(RailsTile {
+                station: Some(ref mut building_tile),
+                ..
+            })) => Some(building_tile),
+            _ => None,
+        }
+    }
+
+    /// The tiles `building` would cover with the north-west corner of its footprint
+    /// at `origin`. Stations run along the track at `origin`.
+    pub fn footprint(&self, origin: Coord2, building: Building) -> Vec<Coord2> {
+        let mut size = building.footprint();
+        if building == Building::TrainStation {
+            if let Some(&Tile::Rails(RailsTile {
+                shape: RailShape::Straight(Orientation::Horizontal),
+                ..
+            })) = self.get(origin)
+            {
+                size = size.flip();
+            }
+        }
+        let mut parts = Vec::with_capacity((size.x * size.y) as usize);
+        for y in origin.y..origin.y + size.y {
+            for x in origin.x..origin.x + size.x {
+                parts.push(Coord2 { x, y });
+            }
+        }
+        parts
+    }
+
+    /// Every tile of the building built at `origin`, as it stands on the map.
+    pub fn building_tiles(&self, origin: Coord2) -> Vec<Coord2> {
+        if self.building_at(origin).is_none() {
+            return vec![];
+        }
+        let mut parts = vec![origin];
+        let mut i = 0;
+        while i < parts.len() {
+            for neighbour in parts[i].neighbours() {
+                let same_building = self.building_at(neighbour).map(|b| b.origin) == Some(origin);
+                if same_building && !parts.contains(&neighbour) {
+                    parts.push(neighbour);
+                }
+            }
+            i += 1;
+        }
+        parts
+    }
+
+    /// Start recording every tile overwritten and every building indexed or
+    /// unindexed, until `end_journal`.
+    fn begin_journal(&mut self) {
+        self.journal = Some(history::Journal::default());
+    }
+
+    /// Stop recording, returning everything changed since `begin_journal`.
+    fn end_journal(&mut self) -> history::Journal {
+        self.journal.take().unwrap_or_default()
+    }
+
+    fn record(&mut self, location: Coord2) {
+        if let Some(ref mut journal) = self.journal {
+            if journal.recorded.insert(location) {
+                journal.tiles.push((location, self.tiles.get(location).cloned()));
+            }
+        }
+    }
+
+    // All changes to tiles go through these, so that edits can be journalled.
+
+    fn tile_mut(&mut self, location: Coord2) -> Option<&mut Tile> {
+        self.record(location);
+        self.tiles.get_mut(location)
+    }
+
+    fn insert_tile(&mut self, location: Coord2, tile: Tile) {
+        self.record(location);
+        self.tiles.insert(location, tile);
+    }
+
+    fn remove_tile(&mut self, location: Coord2) {
+        self.record(location);
+        self.tiles.remove(location);
+    }
+
+    pub fn is_bounded(&self, location: Coord2) -> bool {
+        location.x < self.dimensions.x && location.y < self.dimensions.y
+    }
+
+    fn check_bounded(&self, location: Coord2) -> Result<(), MapError> {
+        if self.is_bounded(location) {
+            Ok(())
+        } else {
+            Err(MapError::OutOfBounds(location))
+        }
+    }
+
+    pub fn can_build(&self, location: Coord2, building: Building) -> bool {
+        self.check_build(location, building).is_ok()
+    }
+
+    pub fn check_build(&self, location: Coord2, building: Building) -> Result<(), MapError> {
+        self.check_bounded(location)?;
+        if building == Building::TrainStation {
+            return self.check_station(location);
+        }
+        let footprint = self.footprint(location, building);
+        for &part in &footprint {
+            self.check_bounded(part)?;
+            if self.get(part).is_some() {
+                return Err(MapError::Occupied(part));
+            }
+            self.check_terrain(part, Terrain::is_buildable)?;
+        }
+        for part in &footprint {
+            for neighbour in part.neighbours() {
+                // Must not be:
+                // - Road
+                // - Building
+                // - Entryway
+                // - Railway
+                // At least for now, anything new we add will also count.
+                if !footprint.contains(&neighbour) && self.get(neighbour).is_some() {
+                    return Err(MapError::Occupied(neighbour));
+                }
+            }
+        }
+        // Must have a nearby road to build an entrance from.
+        if self.entryways_to_build_from(location, building).is_empty() {
+            return Err(MapError::NoAdjacentRoad(location));
+        }
+        Ok(())
+    }
+
+    pub fn can_pave(&self, location: Coord2) -> bool {
+        self.check_pave(location).is_ok()
+    }
+
+    pub fn check_pave(&self, location: Coord2) -> Result<(), MapError> {
+        self.check_bounded(location)?;
+        if self.get(location).is_some() {
+            return Err(MapError::Occupied(location));
+        }
+        self.check_terrain(location, Terrain::is_pavable)?;
+        match self.building_neighbour(location) {
+            Some(neighbour) => Err(MapError::Occupied(neighbour)),
+            None => Ok(()),
+        }
+    }
+
+    pub fn can_walk(&self, location: Coord2) -> bool {
+        if !self.is_bounded(location) {
+            return false;
+        }
+        match self.get(location) {
+            Some(Tile::Paving { .. }) => true,
+            None => self.terrain(location).is_walkable(),
+            _ => false,
+        }
+    }
+
+    pub fn neighbours_a_building(&self, location: Coord2) -> bool {
+        self.building_neighbour(location).is_some()
+    }
+
+    fn building_neighbour(&self, location: Coord2) -> Option<Coord2> {
+        for neighbour in location.neighbours() {
+            // Must not be:
+            // - Building
+            // - Entryway
+            // - Railway
+            // At least for now, anything new we add will also count.
+            match self.get(neighbour) {
+                None | Some(&Tile::Paving { .. }) => {}
+                _ => return Some(neighbour),
+            }
+        }
+        None
+    }
+
+    fn neighbouring_pavings(&self, location: Coord2) -> Neighbours {
+        self.neighbouring(location, |tile| tile.as_paving().is_some())
+    }
+
+    fn neighbouring_rails(&self, location: Coord2) -> Neighbours {
+        self.neighbouring(location, |tile| tile.as_rails().is_some())
+    }
+
+    fn neighbouring<F>(&self, location: Coord2, matches: F) -> Neighbours
+    where
+        F: Fn(&Tile) -> bool,
+    {
+        let mut neighbours = Neighbours::new();
+        for &side in Direction::all().iter() {
+            if let Some(tile) = location.step(side).and_then(|l| self.get(l)) {
+                if matches(tile) {
+                    neighbours.insert(side);
+                }
+            }
+        }
+        neighbours
+    }
+
+    pub fn can_rail(&self, location: Coord2) -> bool {
+        self.check_rail(location).is_ok()
+    }
+
+    pub fn check_rail(&self, location: Coord2) -> Result<(), MapError> {
+        self.check_bounded(location)?;
+        if self.get(location).is_some() {
+            return Err(MapError::Occupied(location));
+        }
+        self.check_terrain(location, Terrain::is_railable)?;
+        for neighbour in location.neighbours() {
+            // Rails join onto other rails, but must keep clear of everything else
+            // just as pavings keep clear of rails.
+            match self.get(neighbour) {
+                None | Some(&Tile::Rails { .. }) => {}
+                _ => return Err(MapError::Occupied(neighbour)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Stations go along a straight run of rails either way, apart from other
+    /// stations, with an entrance from a road two tiles away to one side of the track.
+    fn check_station(&self, location: Coord2) -> Result<(), MapError> {
+        let track = match self.get(location) {
+            Some(&Tile::Rails(RailsTile {
+                station: Some(_), ..
+            })) => return Err(MapError::Occupied(location)),
+            Some(&Tile::Rails(RailsTile {
+                shape: RailShape::Straight(track),
+                ..
+            })) => track,
+            _ => return Err(MapError::NotOnRails(location)),
+        };
+        let footprint = self.footprint(location, Building::TrainStation);
+        for &part in &footprint {
+            self.check_bounded(part)?;
+            match self.get(part) {
+                Some(&Tile::Rails(RailsTile {
+                    station: Some(_), ..
+                })) => return Err(MapError::Occupied(part)),
+                Some(&Tile::Rails(RailsTile {
+                    shape: RailShape::Straight(orientation),
+                    ..
+                })) if orientation == track => {}
+                _ => return Err(MapError::NotOnRails(part)),
+            }
+            // Platforms cannot stand on bridges.
+            self.check_terrain(part, Terrain::is_buildable)?;
+        }
+        // Keep stations apart, so that each stays distinct.
+        for part in &footprint {
+            for neighbour in part.neighbours() {
+                if !footprint.contains(&neighbour) && self.building_at(neighbour).is_some() {
+                    return Err(MapError::Occupied(neighbour));
+                }
+            }
+        }
+        if self
+            .entryways_to_build_from(location, Building::TrainStation)
+            .is_empty()
+        {
+            return Err(MapError::NoAdjacentRoad(location));
+        }
+        Ok(())
+    }
+
+    /// Whether `location` is on east-west rails running unbroken from the west edge
+    /// of the map, as the train runs along.
+    pub fn on_the_line(&self, location: Coord2) -> bool {
+        let along = self.get(location).and_then(Tile::as_rails).map(|r| r.shape)
+            == Some(RailShape::Straight(Orientation::Horizontal));
+        along && (0..location.x).all(|x| {
+            self.get(Coord2 { x, y: location.y })
+                .and_then(Tile::as_rails)
+                .map_or(false, |rails_tile| rails_tile.rails.contains(Direction::East))
+        })
+    }
+
+    /// Where the entrance of `building` at `location` could go, with the side of
+    /// the building it would face, in the order `build` prefers them.
+    pub fn entrances(&self, location: Coord2, building: Building) -> Vec<(Coord2, Direction)> {
+        self.entryways_to_build_from(location, building)
+            .into_iter()
+            .map(|(_, entryway_pos, facing)| (entryway_pos, facing))
+            .collect()
+    }
+
+    /// Where `build_facing` would put the entrance, and the side it would face.
+    pub fn entrance_facing(
+        &self,
+        location: Coord2,
+        building: Building,
+        facing: Option<Direction>,
+    ) -> Option<(Coord2, Direction)> {
+        let entrances = self.entrances(location, building);
+        facing
+            .and_then(|facing| entrances.iter().find(|e| e.1 == facing))
+            .or_else(|| entrances.first())
+            .cloned()
+    }
+
+    /// Each way an entrance could join `building` at `location` to a road, as the
+    /// road, the entrance and the side of the building it faces. Entrances lead
+    /// straight out from an edge of the footprint, trying the north edge first, then
+    /// south, east and west.
+    fn entryways_to_build_from(
+        &self,
+        location: Coord2,
+        building: Building,
+    ) -> Vec<(Coord2, Coord2, Direction)> {
+        let footprint = self.footprint(location, building);
+        let mut entryways = vec![];
+        for &side in &[
+            Direction::North,
+            Direction::South,
+            Direction::East,
+            Direction::West,
+        ] {
+            for part in &footprint {
+                let entryway_pos = match part.step(side) {
+                    Some(l) if !footprint.contains(&l) => l,
+                    _ => continue,
+                };
+                let road_pos = match entryway_pos.step(side) {
+                    Some(l) => l,
+                    None => continue,
+                };
+                let enterable = self.get(road_pos).and_then(Tile::as_paving).is_some()
+                    && self.terrain(entryway_pos).is_buildable();
+                if enterable {
+                    entryways.push((road_pos, entryway_pos, side));
+                }
+            }
+        }
+        if building == Building::TrainStation {
+            // Station entrances leave the track sideways, onto clear ground.
+            let track = match self.get(location) {
+                Some(&Tile::Rails(RailsTile {
+                    shape: RailShape::Straight(track),
+                    ..
+                })) => track,
+                _ => return vec![],
+            };
+            entryways.retain(|&(_, entryway_pos, facing)| {
+                facing.orientation() != track && self.get(entryway_pos).is_none()
+            });
+        }
+        entryways
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..self.dimensions.y {
+            for x in 0..self.dimensions.x {
+                if let Some(tile) = self.get(Coord2 { x, y }) {
+                    write!(f, "{}", tile)?;
+                } else {
+                    write!(f, " ")?;
+                }
+            }
+            write!(f, "\n")?;
+        }
+        Ok(())
+    }
+}
+
+/// A 20 by 20 map with a street running down from (10, 3) to (10, 9), and each
+/// of `buildings` built beside it, for tests to start from.
+#[cfg(test)]
+pub(crate) fn map_with_street(buildings: &[(Coord2, Building)]) -> Map {
+    let mut map = Map::new(Coord2 { x: 20, y: 20 });
+    for y in 3..10 {
+        map.pave(Coord2 { x: 10, y }).unwrap();
+    }
+    for &(origin, building) in buildings {
+        map.build(origin, building).unwrap();
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buildings_face_the_chosen_side() {
+        let mut map = Map::new(Coord2 { x: 12, y: 8 });
+        for location in vec![(7, 2), (8, 2), (6, 3), (6, 4), (6, 5)] {
+            map.pave(Coord2 {
+                x: location.0,
+                y: location.1,
+            }).unwrap();
+        }
+        let house = Coord2 { x: 8, y: 4 };
+        let north = (Coord2 { x: 8, y: 3 }, Direction::North);
+        let west = (Coord2 { x: 7, y: 4 }, Direction::West);
+        assert_eq!(map.entrances(house, Building::House), vec![north, west]);
+        assert_eq!(map.entrance_facing(house, Building::House, None), Some(north));
+        assert_eq!(
+            map.entrance_facing(house, Building::House, Some(Direction::South)),
+            Some(north)
+        );
+
+        map.build_facing(house, Building::House, Some(Direction::West))
+            .unwrap();
+        assert_eq!(map.building_at(house).unwrap().entryway_pos, west.0);
+        assert_eq!(map.get(north.0), None);
+        assert_eq!(map.validate(), vec![]);
+    }
+
+    #[test]
+    fn paths_are_laid_whole_or_not_at_all() {
+        let mut map = Map::new(Coord2 { x: 12, y: 8 });
+        map.rail(Coord2 { x: 9, y: 5 }).unwrap();
+        let before = map.to_string();
+        let blocked = Path::l_shaped(Coord2 { x: 6, y: 2 }, Coord2 { x: 9, y: 7 });
+        assert_eq!(
+            map.pave_paths(&blocked),
+            Err(MapError::Occupied(Coord2 { x: 9, y: 5 }))
+        );
+        assert_eq!(map.to_string(), before);
+
+        let corner = Path::l_shaped(Coord2 { x: 6, y: 2 }, Coord2 { x: 8, y: 4 });
+        map.pave_paths(&corner).unwrap();
+        let road = map.get(Coord2 { x: 8, y: 2 }).and_then(Tile::as_paving).unwrap();
+        assert_eq!(
+            road.pavings.directions(),
+            vec![Direction::South, Direction::West]
+        );
+        assert_eq!(map.validate(), vec![]);
+    }
+
+    #[test]
+    fn roads_bridge_water_but_not_hills() {
+        let mut map = Map::new(Coord2 { x: 12, y: 8 });
+        let water = Coord2 { x: 6, y: 4 };
+        let hill = Coord2 { x: 6, y: 5 };
+        map.set_terrain(water, Terrain::Water).unwrap();
+        map.set_terrain(hill, Terrain::Hills).unwrap();
+        map.pave(Coord2 { x: 6, y: 3 }).unwrap();
+        map.pave(water).unwrap();
+        assert!(map.is_bridge(water));
+        assert_eq!(
+            map.pave(hill),
+            Err(MapError::UnsuitableTerrain(hill, Terrain::Hills))
+        );
+        assert_eq!(map.get(hill), None);
+    }
+
+    #[test]
+    fn stations_run_along_straight_track_either_way() {
+        let mut map = Map::new(Coord2 { x: 16, y: 14 });
+        // Join the road already laid at (8, 2).
+        for x in (0..8).chain(9..16) {
+            map.pave(Coord2 { x, y: 2 }).unwrap();
+        }
+        for x in 2..16 {
+            map.rail(Coord2 { x, y: 4 }).unwrap();
+        }
+        let across = Coord2 { x: 5, y: 4 };
+        map.build(across, Building::TrainStation).unwrap();
+        assert_eq!(map.building_tiles(across).len(), 4);
+        assert!((5..9).all(|x| map.building_at(Coord2 { x, y: 4 }).is_some()));
+        // The train only runs along lines from the west edge.
+        assert!(!map.on_the_line(across));
+        for x in 0..2 {
+            map.rail(Coord2 { x, y: 4 }).unwrap();
+        }
+        assert!(map.on_the_line(across));
+
+        for y in 6..12 {
+            map.rail(Coord2 { x: 3, y }).unwrap();
+            map.pave(Coord2 { x: 5, y }).unwrap();
+        }
+        let down = Coord2 { x: 3, y: 7 };
+        let spur = Coord2 { x: 2, y: 9 };
+        map.rail(spur).unwrap();
+        assert_eq!(
+            map.build(down, Building::TrainStation),
+            Err(MapError::NotOnRails(Coord2 { x: 3, y: 9 }))
+        );
+        map.delete(spur).unwrap();
+        map.build(down, Building::TrainStation).unwrap();
+        let platform: Vec<Coord2> = (7..11).map(|y| Coord2 { x: 3, y }).collect();
+        assert_eq!(map.footprint(down, Building::TrainStation), platform);
+        assert!(platform.iter().all(|part| map.building_at(*part).is_some()));
+        let entrance = map.building_at(down).unwrap().entryway_pos;
+        assert_eq!(entrance.x, 4);
+        assert!(!map.on_the_line(down));
+        assert_eq!(map.validate(), vec![]);
+    }
+
+    #[test]
+    fn rails_keep_clear_of_other_tiles_and_of_hills() {
+        let mut map = Map::new(Coord2 { x: 12, y: 12 });
+        let road = Coord2 { x: 6, y: 2 };
+        assert_eq!(
+            map.rail(Coord2 { x: 6, y: 3 }),
+            Err(MapError::Occupied(road))
+        );
+        assert_eq!(map.rail(road), Err(MapError::Occupied(road)));
+        let hill = Coord2 { x: 2, y: 8 };
+        map.set_terrain(hill, Terrain::Hills).unwrap();
+        assert_eq!(
+            map.rail(hill),
+            Err(MapError::UnsuitableTerrain(hill, Terrain::Hills))
+        );
+        map.rail(Coord2 { x: 2, y: 7 }).unwrap();
+        assert_eq!(
+            map.pave(Coord2 { x: 3, y: 7 }),
+            Err(MapError::Occupied(Coord2 { x: 2, y: 7 }))
+        );
+    }
+
+    #[test]
+    fn rails_take_their_shape_from_their_neighbours() {
+        let mut map = Map::new(Coord2 { x: 12, y: 12 });
+        let shape = |map: &Map, x, y| {
+            map.get(Coord2 { x, y })
+                .and_then(Tile::as_rails)
+                .unwrap()
+                .shape
+        };
+        for x in 2..7 {
+            map.rail(Coord2 { x, y: 6 }).unwrap();
+        }
+        // A branch down from the middle makes a T junction, and one up from the
+        // end a corner.
+        for y in 7..10 {
+            map.rail(Coord2 { x: 4, y }).unwrap();
+        }
+        map.rail(Coord2 { x: 6, y: 5 }).unwrap();
+        assert_eq!(shape(&map, 2, 6), RailShape::Straight(Orientation::Horizontal));
+        assert_eq!(shape(&map, 4, 6), RailShape::Junction(Direction::North));
+        assert_eq!(shape(&map, 4, 8), RailShape::Straight(Orientation::Vertical));
+        assert_eq!(
+            shape(&map, 6, 6),
+            RailShape::Corner(Direction::North, Direction::West)
+        );
+        map.rail(Coord2 { x: 4, y: 5 }).unwrap();
+        assert_eq!(shape(&map, 4, 6), RailShape::Crossing);
+        assert_eq!(map.validate(), vec![]);
+    }
+
+    #[test]
+    fn deleting_an_arm_of_a_junction_reshapes_what_is_left() {
+        let mut map = Map::new(Coord2 { x: 12, y: 12 });
+        for x in 2..7 {
+            map.rail(Coord2 { x, y: 6 }).unwrap();
+        }
+        for y in 7..10 {
+            map.rail(Coord2 { x: 4, y }).unwrap();
+        }
+        let (junction, arm) = (Coord2 { x: 4, y: 6 }, Coord2 { x: 4, y: 7 });
+        map.delete(arm).unwrap();
+        assert_eq!(map.get(arm), None);
+
+        let rails_tile = map.get(junction).and_then(Tile::as_rails).unwrap();
+        assert_eq!(rails_tile.shape, RailShape::Straight(Orientation::Horizontal));
+        assert_eq!(
+            rails_tile.rails.directions(),
+            vec![Direction::East, Direction::West]
+        );
+        // The rest of the arm keeps running down, now joined only below.
+        let rails_tile = map.get(Coord2 { x: 4, y: 8 }).and_then(Tile::as_rails).unwrap();
+        assert_eq!(rails_tile.shape, RailShape::Straight(Orientation::Vertical));
+        assert_eq!(rails_tile.rails.directions(), vec![Direction::South]);
+        assert_eq!(map.validate(), vec![]);
+    }
+
+    #[test]
+    fn edits_off_the_map_are_out_of_bounds() {
+        let mut map = Map::new(Coord2 { x: 12, y: 8 });
+        let off = Coord2 { x: 12, y: 3 };
+        let before = map.to_string();
+        assert_eq!(map.pave(off), Err(MapError::OutOfBounds(off)));
+        assert_eq!(map.rail(off), Err(MapError::OutOfBounds(off)));
+        assert_eq!(
+            map.build(off, Building::House),
+            Err(MapError::OutOfBounds(off))
+        );
+        // A footprint hanging off the edge is refused at its first outside tile.
+        assert_eq!(
+            map.build(Coord2 { x: 11, y: 3 }, Building::Saloon),
+            Err(MapError::OutOfBounds(off))
+        );
+        assert_eq!(map.to_string(), before);
+    }
+
+    #[test]
+    fn roads_into_buildings_and_buildings_without_roads_are_refused() {
+        let house = Coord2 { x: 12, y: 4 };
+        let mut map = map_with_street(&[(house, Building::House)]);
+        assert_eq!(
+            map.delete(Coord2 { x: 10, y: 4 }),
+            Err(MapError::WouldOrphanBuilding(house))
+        );
+        assert!(map.get(house).is_some());
+
+        let remote = Coord2 { x: 3, y: 15 };
+        assert_eq!(
+            map.build(remote, Building::House),
+            Err(MapError::NoAdjacentRoad(remote))
+        );
+        assert_eq!(map.get(remote), None);
+    }
+
+    #[test]
+    fn buildings_cover_their_whole_footprint() {
+        let factory = Coord2 { x: 12, y: 4 };
+        let mut map = map_with_street(&[(factory, Building::Factory)]);
+        let footprint = map.footprint(factory, Building::Factory);
+        assert_eq!(footprint.len(), 6);
+        assert!(footprint
+            .iter()
+            .all(|part| map.building_at(*part).map(|b| b.origin) == Some(factory)));
+
+        // Nothing may overlap it, or stand right beside it.
+        assert_eq!(
+            map.build(Coord2 { x: 13, y: 5 }, Building::House),
+            Err(MapError::Occupied(Coord2 { x: 13, y: 5 }))
+        );
+        assert_eq!(
+            map.build(Coord2 { x: 14, y: 6 }, Building::House),
+            Err(MapError::Occupied(Coord2 { x: 13, y: 6 }))
+        );
+
+        let entrance = map.building_at(factory).unwrap().entryway_pos;
+        match route(&map, Coord2 { x: 10, y: 9 }, factory) {
+            Route::Tiles(tiles) => {
+                assert!(tiles.contains(&entrance));
+                assert_eq!(tiles.last(), Some(&factory));
+            }
+            other => panic!("expected a route, got {:?}", other),
+        }
+
+        map.delete(Coord2 { x: 13, y: 6 }).unwrap();
+        assert!(footprint.iter().all(|part| map.get(*part).is_none()));
+        assert_eq!(map.get(entrance), None);
+        assert_eq!(map.validate(), vec![]);
+    }
+}
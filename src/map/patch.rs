@@ -0,0 +1,410 @@
+use super::*;
+use std::str::FromStr;
+
+/// Version written by `MapPatch::to_text`.
+pub const PATCH_FORMAT_VERSION: u64 = 1;
+
+const PATCH_FORMAT_HEADER: &str = "tilewater-patch";
+
+/// What stands on a tile, as far as a patch records it. The rest of a building's
+/// footprint, and its entrance, come with the building and are not listed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PatchTile {
+    Paving(RoadClass),
+    Rails,
+    /// The north-west corner of a building, entered through the entrance at
+    /// `entrance`. A station's rails come with it.
+    Building {
+        building: Building,
+        entrance: Coord2,
+        level: u64,
+    },
+}
+
+impl PatchTile {
+    /// What `map` has at `location`, if a patch would record anything there.
+    fn of(map: &Map, location: Coord2) -> Option<PatchTile> {
+        match *map.get(location)? {
+            Tile::Paving(PavingTile { class, .. }) => Some(PatchTile::Paving(class)),
+            Tile::Building(ref building_tile)
+            | Tile::Rails(RailsTile {
+                station: Some(ref building_tile),
+                ..
+            }) if building_tile.origin == location => Some(PatchTile::Building {
+                building: building_tile.building,
+                entrance: building_tile.entryway_pos,
+                level: building_tile.level,
+            }),
+            Tile::Rails(_) => Some(PatchTile::Rails),
+            Tile::Building(_) | Tile::Entrance(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for PatchTile {
+    /// Paving as its road class code and rails as `=`. A building is its code, level
+    /// and entrance, as in `h2@11,4`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PatchTile::Paving(class) => write!(f, "{}", class.code()),
+            PatchTile::Rails => write!(f, "="),
+            PatchTile::Building {
+                building,
+                entrance,
+                level,
+            } => write!(
+                f,
+                "{}{}@{},{}",
+                building.code(),
+                level,
+                entrance.x,
+                entrance.y
+            ),
+        }
+    }
+}
+
+impl FromStr for PatchTile {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<PatchTile, ()> {
+        let mut chars = s.chars();
+        let code = chars.next().ok_or(())?;
+        let rest = chars.as_str();
+        if let Some(class) = RoadClass::from_code(code) {
+            return if rest.is_empty() {
+                Ok(PatchTile::Paving(class))
+            } else {
+                Err(())
+            };
+        }
+        if code == '=' && rest.is_empty() {
+            return Ok(PatchTile::Rails);
+        }
+        let building = Building::from_code(code).ok_or(())?;
+        let (level, entrance) = match rest.find('@') {
+            Some(at) => (&rest[..at], &rest[at + 1..]),
+            None => return Err(()),
+        };
+        let level: u64 = level.parse().map_err(|_| ())?;
+        if level < 1 || level > MAX_BUILDING_LEVEL {
+            return Err(());
+        }
+        let entrance: Vec<&str> = entrance.split(',').collect();
+        Ok(PatchTile::Building {
+            building,
+            entrance: parse_coord2(&entrance).ok_or(())?,
+            level,
+        })
+    }
+}
+
+/// A change to one tile between two versions of a map.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TileChange {
+    Added(Coord2, PatchTile),
+    Removed(Coord2, PatchTile),
+    Changed(Coord2, PatchTile, PatchTile),
+}
+
+impl TileChange {
+    pub fn location(&self) -> Coord2 {
+        match *self {
+            TileChange::Added(location, _)
+            | TileChange::Removed(location, _)
+            | TileChange::Changed(location, _, _) => location,
+        }
+    }
+
+    pub fn before(&self) -> Option<PatchTile> {
+        match *self {
+            TileChange::Added(_, _) => None,
+            TileChange::Removed(_, before) | TileChange::Changed(_, before, _) => Some(before),
+        }
+    }
+
+    pub fn after(&self) -> Option<PatchTile> {
+        match *self {
+            TileChange::Removed(_, _) => None,
+            TileChange::Added(_, after) | TileChange::Changed(_, _, after) => Some(after),
+        }
+    }
+}
+
+/// The tiles which differ between two maps, as made by `Map::diff`. Terrain, zones
+/// and the dimensions are not compared.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MapPatch {
+    /// In order of location, row by row.
+    pub changes: Vec<TileChange>,
+}
+
+impl Map {
+    /// The patch which turns this map into `other`.
+    pub fn diff(&self, other: &Map) -> MapPatch {
+        let mut locations: Vec<Coord2> = self
+            .tiles()
+            .chain(other.tiles())
+            .map(|(location, _)| location)
+            .collect();
+        locations.sort_by_key(|location| (location.y, location.x));
+        locations.dedup();
+        let changes = locations
+            .into_iter()
+            .filter_map(|location| {
+                match (
+                    PatchTile::of(self, location),
+                    PatchTile::of(other, location),
+                ) {
+                    (None, Some(after)) => Some(TileChange::Added(location, after)),
+                    (Some(before), None) => Some(TileChange::Removed(location, before)),
+                    (Some(before), Some(after)) if before != after => {
+                        Some(TileChange::Changed(location, before, after))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+        MapPatch { changes }
+    }
+}
+
+impl MapPatch {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Make the changes to `map` through the usual rules for building, paving and
+    /// laying rails. If any is refused, or `map` does not have what the patch
+    /// expects to change, the map is left as it was.
+    pub fn apply(&self, map: &mut Map) -> Result<(), MapError> {
+        map.begin_journal();
+        let result = self.replay(map);
+        let mut journal = map.end_journal();
+        match result {
+            Ok(()) => map.commit(&mut journal),
+            Err(_) => map.roll_back(journal),
+        }
+        result
+    }
+
+    /// Clear away buildings and then the ground under them, lay new ground, then
+    /// build on it, so that each step has what it needs in place.
+    fn replay(&self, map: &mut Map) -> Result<(), MapError> {
+        for change in &self.changes {
+            if PatchTile::of(map, change.location()) != change.before() {
+                return Err(MapError::PatchMismatch(change.location()));
+            }
+        }
+        for change in &self.changes {
+            if moved(change) && building(change.before()).is_some() {
+                map.delete_cascading(change.location())?;
+            }
+        }
+        for change in &self.changes {
+            let (before, after) = (change.before(), change.after());
+            let unpaved = class(before).is_some() && class(after).is_none();
+            if unpaved || (has_rails(before) && !has_rails(after)) {
+                map.delete_cascading(change.location())?;
+            }
+        }
+        for change in &self.changes {
+            let (before, after) = (change.before(), change.after());
+            if class(after).is_some() && class(before).is_none() {
+                map.pave(change.location())?;
+            }
+            if has_rails(after) && !has_rails(before) {
+                map.rail(change.location())?;
+            }
+        }
+        for change in &self.changes {
+            let location = change.location();
+            if let Some(class) = class(change.after()) {
+                map.set_road_class(location, class)?;
+            }
+            if let Some((building, entrance)) = building(change.after()) {
+                if moved(change) {
+                    map.build_at_entrance(location, building, entrance)?;
+                }
+            }
+        }
+        for change in &self.changes {
+            if let Some(PatchTile::Building { level, .. }) = change.after() {
+                set_level(map, change.location(), level)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Render in a line-based text form understood by `MapPatch::from_str`. Each
+    /// change is `+`, `-` or `~`, a location, and the tiles before and after.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{} {}\n", PATCH_FORMAT_HEADER, PATCH_FORMAT_VERSION);
+        for change in &self.changes {
+            let line = match *change {
+                TileChange::Added(l, after) => format!("+ {} {} {}\n", l.x, l.y, after),
+                TileChange::Removed(l, before) => format!("- {} {} {}\n", l.x, l.y, before),
+                TileChange::Changed(l, before, after) => {
+                    format!("~ {} {} {} {}\n", l.x, l.y, before, after)
+                }
+            };
+            text.push_str(&line);
+        }
+        text
+    }
+}
+
+impl FromStr for MapPatch {
+    type Err = ParseMapError;
+
+    fn from_str(s: &str) -> Result<MapPatch, ParseMapError> {
+        let mut lines = s.lines();
+        let header = lines.next().unwrap_or("");
+        if !header.starts_with(PATCH_FORMAT_HEADER) {
+            return Err(ParseMapError::BadRecord {
+                line: 1,
+                record: header.to_string(),
+            });
+        }
+        let version = header[PATCH_FORMAT_HEADER.len()..].trim();
+        if version.parse() != Ok(PATCH_FORMAT_VERSION) {
+            return Err(ParseMapError::UnsupportedVersion(version.to_string()));
+        }
+
+        let mut changes = vec![];
+        for (index, line) in lines.enumerate() {
+            let bad_record = || ParseMapError::BadRecord {
+                line: index + 2,
+                record: line.to_string(),
+            };
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.is_empty() {
+                continue;
+            }
+            if fields.len() < 4 {
+                return Err(bad_record());
+            }
+            let location = parse_coord2(&fields[1..3]).ok_or_else(bad_record)?;
+            let tiles = fields[3..]
+                .iter()
+                .map(|field| field.parse::<PatchTile>())
+                .collect::<Result<Vec<_>, ()>>()
+                .map_err(|_| bad_record())?;
+            let change = match (fields[0], &tiles[..]) {
+                ("+", &[after]) => TileChange::Added(location, after),
+                ("-", &[before]) => TileChange::Removed(location, before),
+                ("~", &[before, after]) => TileChange::Changed(location, before, after),
+                _ => return Err(bad_record()),
+            };
+            changes.push(change);
+        }
+        Ok(MapPatch { changes })
+    }
+}
+
+fn class(tile: Option<PatchTile>) -> Option<RoadClass> {
+    match tile {
+        Some(PatchTile::Paving(class)) => Some(class),
+        _ => None,
+    }
+}
+
+fn has_rails(tile: Option<PatchTile>) -> bool {
+    match tile {
+        Some(PatchTile::Rails)
+        | Some(PatchTile::Building {
+            building: Building::TrainStation,
+            ..
+        }) => true,
+        _ => false,
+    }
+}
+
+/// The building and its entrance, leaving out the level, which can change in place.
+fn building(tile: Option<PatchTile>) -> Option<(Building, Coord2)> {
+    match tile {
+        Some(PatchTile::Building {
+            building, entrance, ..
+        }) => Some((building, entrance)),
+        _ => None,
+    }
+}
+
+/// Whether the building at a location, if any, must be built anew.
+fn moved(change: &TileChange) -> bool {
+    building(change.before()) != building(change.after())
+}
+
+fn set_level(map: &mut Map, origin: Coord2, level: u64) -> Result<(), MapError> {
+    loop {
+        let current = map
+            .building_at(origin)
+            .ok_or(MapError::NoBuilding(origin))?
+            .level;
+        if current == level {
+            return Ok(());
+        } else if current < level {
+            map.upgrade_building(origin)?;
+        } else {
+            map.downgrade_building(origin)?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patches_replay_the_diff_and_round_trip_as_text() {
+        let before = map_with_street(&[(Coord2 { x: 12, y: 4 }, Building::House)]);
+        let mut after = before.clone();
+        after.delete_cascading(Coord2 { x: 12, y: 4 }).unwrap();
+        after
+            .build(Coord2 { x: 7, y: 6 }, Building::Saloon)
+            .unwrap();
+        after.upgrade_building(Coord2 { x: 7, y: 6 }).unwrap();
+        after.upgrade_road(Coord2 { x: 10, y: 5 }).unwrap();
+        after.delete(Coord2 { x: 10, y: 9 }).unwrap();
+        for x in 2..18 {
+            after.rail(Coord2 { x, y: 0 }).unwrap();
+        }
+
+        let patch = before.diff(&after);
+        assert!(patch.changes.contains(&TileChange::Changed(
+            Coord2 { x: 10, y: 5 },
+            PatchTile::Paving(RoadClass::Street),
+            PatchTile::Paving(RoadClass::Avenue)
+        )));
+        let text = patch.to_text();
+        let parsed: MapPatch = text.parse().unwrap();
+        assert_eq!(parsed, patch);
+
+        let mut replayed = before.clone();
+        parsed.apply(&mut replayed).unwrap();
+        assert_eq!(replayed.to_text(), after.to_text());
+        assert!(replayed.diff(&after).is_empty());
+
+        // A refused patch leaves the map untouched.
+        let mut unchanged = before.clone();
+        let clash = MapPatch {
+            changes: vec![
+                TileChange::Added(Coord2 { x: 2, y: 2 }, PatchTile::Rails),
+                TileChange::Added(Coord2 { x: 2, y: 3 }, PatchTile::Paving(RoadClass::Street)),
+            ],
+        };
+        assert_eq!(
+            clash.apply(&mut unchanged),
+            Err(MapError::Occupied(Coord2 { x: 2, y: 2 }))
+        );
+        assert_eq!(unchanged.to_text(), before.to_text());
+
+        // As does one made against some other map.
+        let mut patched = after.clone();
+        assert_eq!(
+            patch.apply(&mut patched),
+            Err(MapError::PatchMismatch(patch.changes[0].location()))
+        );
+        assert_eq!(patched.to_text(), after.to_text());
+    }
+}
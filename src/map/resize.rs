@@ -0,0 +1,114 @@
+use super::*;
+
+impl Map {
+    /// Grow the map by `amount` tiles on its `direction` side. Growing north or west
+    /// moves everything already on the map south or east, so returns the offset it all
+    /// moved by, for `Agents::shift` to move the agents with it.
+    pub fn expand(&mut self, direction: Direction, amount: u64) -> (i64, i64) {
+        let (dimensions, offset) = self.resized(direction, amount as i64);
+        self.reshape(dimensions, offset);
+        offset
+    }
+
+    /// Shrink the map by `amount` tiles from its `direction` side, so long as no tile
+    /// stands there. Any terrain and zones there are lost. Returns the offset as
+    /// `expand` does.
+    pub fn shrink(&mut self, direction: Direction, amount: u64) -> Result<(i64, i64), MapError> {
+        let extent = match direction.orientation() {
+            Orientation::Vertical => self.dimensions.y,
+            Orientation::Horizontal => self.dimensions.x,
+        };
+        // Something must be left, if only for the cursor.
+        if amount >= extent {
+            return Err(MapError::WouldCutOff(self.cursor));
+        }
+        let (dimensions, offset) = self.resized(direction, -(amount as i64));
+        for (location, _) in self.tiles() {
+            let shifted = location.shifted(offset);
+            let kept = location.x as i64 + offset.0 >= 0
+                && location.y as i64 + offset.1 >= 0
+                && shifted.x < dimensions.x
+                && shifted.y < dimensions.y;
+            if !kept {
+                return Err(MapError::WouldCutOff(location));
+            }
+        }
+        self.reshape(dimensions, offset);
+        Ok(offset)
+    }
+
+    /// The dimensions after moving the `direction` edge outwards by `change` tiles,
+    /// and the offset which keeps everything else where it was.
+    fn resized(&self, direction: Direction, change: i64) -> (Coord2, (i64, i64)) {
+        let (dx, dy) = direction.as_offset();
+        let dimensions = Coord2 {
+            x: (self.dimensions.x as i64 + dx.abs() * change) as u64,
+            y: (self.dimensions.y as i64 + dy.abs() * change) as u64,
+        };
+        let offset = match direction {
+            Direction::North | Direction::West => (-dx * change, -dy * change),
+            Direction::East | Direction::South => (0, 0),
+        };
+        (dimensions, offset)
+    }
+
+    /// Change to `dimensions`, moving every tile, layer and recorded location by
+    /// `offset`.
+    fn reshape(&mut self, dimensions: Coord2, offset: (i64, i64)) {
+        self.dimensions = dimensions;
+        self.tiles.reshape(dimensions, offset);
+        let locations: Vec<Coord2> = self.tiles.iter().map(|(l, _)| l).collect();
+        for location in locations {
+            self.tiles.get_mut(location).unwrap().shift(offset);
+        }
+        self.terrain.reshape(dimensions, offset);
+        self.zones.reshape(dimensions, offset);
+        for origins in self.buildings.values_mut() {
+            for origin in origins.iter_mut() {
+                *origin = origin.shifted(offset);
+            }
+        }
+        self.reindex_buildings();
+        let cursor = self.cursor.shifted(offset);
+        self.cursor = Coord2 {
+            x: cursor.x.min(dimensions.x - 1),
+            y: cursor.y.min(dimensions.y - 1),
+        };
+        self.survey_land_value();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expanding_moves_everything_and_shrinking_keeps_tiles() {
+        let mut map = map_with_street(&[(Coord2 { x: 12, y: 4 }, Building::House)]);
+
+        assert_eq!(map.expand(Direction::West, 5), (5, 0));
+        assert_eq!(map.expand(Direction::North, 3), (0, 3));
+        assert_eq!(map.expand(Direction::South, 2), (0, 0));
+        assert_eq!((map.width(), map.height()), (25, 25));
+        assert!(map.validate().is_empty());
+        assert_eq!(
+            map.buildings[&Building::House],
+            vec![Coord2 { x: 17, y: 7 }]
+        );
+        assert!(map
+            .get(Coord2 { x: 15, y: 5 })
+            .and_then(Tile::as_paving)
+            .is_some());
+
+        assert_eq!(map.shrink(Direction::West, 5), Ok((-5, 0)));
+        assert_eq!(
+            map.shrink(Direction::West, 11),
+            Err(MapError::WouldCutOff(Coord2 { x: 10, y: 5 }))
+        );
+        assert_eq!(
+            map.buildings[&Building::House],
+            vec![Coord2 { x: 12, y: 7 }]
+        );
+        assert!(map.validate().is_empty());
+    }
+}
@@ -0,0 +1,207 @@
+use super::*;
+
+/// Width and height of the square cells buildings are bucketed into.
+const CELL_SIZE: u64 = 8;
+
+/// The origins in `Map.buildings`, bucketed by type and by cell, for finding the
+/// buildings near a location without looking at every one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(super) struct SpatialIndex {
+    cells: HashMap<(Building, Coord2), Vec<Coord2>>,
+}
+
+impl SpatialIndex {
+    /// An index of every building in `buildings`.
+    pub(super) fn of(buildings: &BTreeMap<Building, Vec<Coord2>>) -> SpatialIndex {
+        let mut index = SpatialIndex::default();
+        for (&building, origins) in buildings {
+            for &origin in origins {
+                index.insert(building, origin);
+            }
+        }
+        index
+    }
+
+    /// Kept in order row by row, so that indexes of the same buildings are equal.
+    pub(super) fn insert(&mut self, building: Building, origin: Coord2) {
+        let origins = self
+            .cells
+            .entry((building, cell_of(origin)))
+            .or_insert_with(Vec::new);
+        if let Err(index) = origins.binary_search_by_key(&(origin.y, origin.x), |o| (o.y, o.x)) {
+            origins.insert(index, origin);
+        }
+    }
+
+    pub(super) fn remove(&mut self, building: Building, origin: Coord2) {
+        let cell = (building, cell_of(origin));
+        let now_empty = match self.cells.get_mut(&cell) {
+            Some(origins) => {
+                origins.retain(|o| *o != origin);
+                origins.is_empty()
+            }
+            None => false,
+        };
+        if now_empty {
+            self.cells.remove(&cell);
+        }
+    }
+
+    /// Every `building` in the cell at (`x`, `y`), counted in cells.
+    fn in_cell(&self, building: Building, x: i64, y: i64) -> &[Coord2] {
+        if x < 0 || y < 0 {
+            return &[];
+        }
+        let cell = Coord2 {
+            x: x as u64,
+            y: y as u64,
+        };
+        self.cells.get(&(building, cell)).map_or(&[], |o| &o[..])
+    }
+}
+
+impl Map {
+    /// Every `building` whose origin is no more than `radius` tiles from `location`
+    /// in a straight line, nearest first.
+    pub fn buildings_within(
+        &self,
+        location: Coord2,
+        radius: u64,
+        building: Building,
+    ) -> Vec<Coord2> {
+        let low = cell_of(Coord2 {
+            x: location.x.saturating_sub(radius),
+            y: location.y.saturating_sub(radius),
+        });
+        let high = cell_of(Coord2 {
+            x: location.x + radius,
+            y: location.y + radius,
+        });
+        let mut found = vec![];
+        for y in low.y..high.y + 1 {
+            for x in low.x..high.x + 1 {
+                found.extend(
+                    self.spatial
+                        .in_cell(building, x as i64, y as i64)
+                        .iter()
+                        .filter(|o| distance_squared(location, **o) <= radius * radius),
+                );
+            }
+        }
+        sort_by_distance(location, &mut found);
+        found
+    }
+
+    /// Up to `count` of `building` with origins nearest `location` in a straight
+    /// line, nearest first.
+    pub fn nearest_buildings(
+        &self,
+        location: Coord2,
+        building: Building,
+        count: usize,
+    ) -> Vec<Coord2> {
+        let centre = cell_of(location);
+        let furthest = (self.dimensions.x.max(self.dimensions.y) / CELL_SIZE + 1) as i64;
+        let mut found = vec![];
+        for ring in 0..furthest + 1 {
+            for (x, y) in ring_cells(centre, ring) {
+                found.extend(self.spatial.in_cell(building, x, y));
+            }
+            // Anything in a further ring is more than `reach` tiles away.
+            if found.len() >= count {
+                sort_by_distance(location, &mut found);
+                let reach = ring as u64 * CELL_SIZE;
+                if count == 0 || distance_squared(location, found[count - 1]) <= reach * reach {
+                    break;
+                }
+            }
+        }
+        sort_by_distance(location, &mut found);
+        found.truncate(count);
+        found
+    }
+
+    /// Rebuild the spatial index after `buildings` has been replaced wholesale.
+    pub(super) fn reindex_buildings(&mut self) {
+        self.spatial = SpatialIndex::of(&self.buildings);
+    }
+}
+
+fn cell_of(location: Coord2) -> Coord2 {
+    Coord2 {
+        x: location.x / CELL_SIZE,
+        y: location.y / CELL_SIZE,
+    }
+}
+
+/// The cells exactly `ring` cells from `centre` across or down, all the way round.
+fn ring_cells(centre: Coord2, ring: i64) -> Vec<(i64, i64)> {
+    let (cx, cy) = (centre.x as i64, centre.y as i64);
+    if ring == 0 {
+        return vec![(cx, cy)];
+    }
+    let mut cells = vec![];
+    for x in cx - ring..cx + ring + 1 {
+        cells.push((x, cy - ring));
+        cells.push((x, cy + ring));
+    }
+    for y in cy - ring + 1..cy + ring {
+        cells.push((cx - ring, y));
+        cells.push((cx + ring, y));
+    }
+    cells
+}
+
+fn distance_squared(a: Coord2, b: Coord2) -> u64 {
+    let dx = a.x.max(b.x) - a.x.min(b.x);
+    let dy = a.y.max(b.y) - a.y.min(b.y);
+    dx * dx + dy * dy
+}
+
+/// Nearest first, breaking ties row by row so that the order is always the same.
+fn sort_by_distance(location: Coord2, origins: &mut [Coord2]) {
+    origins.sort_by_key(|o| (distance_squared(location, *o), o.y, o.x));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearby_buildings_are_found_nearest_first() {
+        let mut map = Map::new(Coord2 { x: 40, y: 40 });
+        for y in 3..38 {
+            map.pave(Coord2 { x: 20, y }).unwrap();
+        }
+        let houses: Vec<Coord2> = (0..6)
+            .map(|i| Coord2 {
+                x: 22,
+                y: 4 + 6 * i,
+            })
+            .collect();
+        for &house in &houses {
+            map.build(house, Building::House).unwrap();
+        }
+        let from = Coord2 { x: 30, y: 17 };
+        assert_eq!(
+            map.nearest_buildings(from, Building::House, 3),
+            vec![houses[2], houses[3], houses[1]]
+        );
+        assert_eq!(
+            map.buildings_within(from, 10, Building::House),
+            vec![houses[2], houses[3]]
+        );
+        assert!(map.nearest_buildings(from, Building::Saloon, 3).is_empty());
+
+        map.delete(houses[2]).unwrap();
+        assert_eq!(
+            map.nearest_buildings(from, Building::House, 1),
+            vec![houses[3]]
+        );
+        map.expand(Direction::West, 10);
+        assert_eq!(
+            map.buildings_within(Coord2 { x: 40, y: 17 }, 10, Building::House),
+            vec![houses[3].shifted((10, 0))]
+        );
+    }
+}
@@ -1,6 +1,5 @@
 use super::*;
 use piston_window::*;
-use std::sync::{mpsc, Arc, RwLock};
 
 const PPU: u64 = 10;
 
@@ -10,30 +9,48 @@ pub enum Cmd {
     Left,
     Right,
     Pave,
+    Rail,
+    /// Drop or lift the anchor which paving and rails are dragged from.
+    Anchor,
+    /// Pick up a building to place, or place it if it is already picked up.
     Build(Building),
+    Place,
+    CycleEntrance,
     Delete,
+    Undo,
+    Redo,
+    Zone(Option<Zone>),
+    ToggleLandValue,
+    UpgradeRoad,
+    DowngradeRoad,
 }
 
 pub struct RenderToPiston {
-    agents: Agents,
+    simulation: Simulation,
     window: PistonWindow,
-    map: Arc<RwLock<Map>>,
-    passenger_tx: mpsc::Sender<Agent>,
+    show_land_value: bool,
+    /// The building being placed at the cursor, if any, and the side it should face.
+    placing: Option<Building>,
+    facing: Option<Direction>,
+    /// Where paths of paving or rails are dragged from to the cursor, if anywhere.
+    anchor: Option<Coord2>,
 }
 
 impl RenderToPiston {
-    pub fn new(
-        agents: Agents,
-        window: PistonWindow,
-        map: Arc<RwLock<Map>>,
-        passenger_tx: mpsc::Sender<Agent>,
-    ) -> RenderToPiston {
-        RenderToPiston {
-            agents,
+    pub fn new(simulation: Simulation, window: PistonWindow) -> RenderToPiston {
+        let mut renderer = RenderToPiston {
+            simulation,
             window,
-            map,
-            passenger_tx,
+            show_land_value: false,
+            placing: None,
+            facing: None,
+            anchor: None,
+        };
+        // A resumed game may have funds already.
+        if renderer.simulation.stats.funds > 0 {
+            renderer.show_funds();
         }
+        renderer
     }
 
     pub fn render_loop(&mut self) {
@@ -44,8 +61,13 @@ impl RenderToPiston {
             }
 
             if let Some(_args) = e.update_args() {
-                let map = self.map.read().unwrap();
-                self.agents.update(&map);
+                let funds = self.simulation.stats.funds;
+                for event in self.simulation.update() {
+                    println!("{}", event);
+                }
+                if self.simulation.stats.funds != funds {
+                    self.show_funds();
+                }
             }
 
             if let Some(_args) = e.render_args() {
@@ -54,6 +76,16 @@ impl RenderToPiston {
         }
     }
 
+    fn show_funds(&mut self) {
+        let title = format!("Tilewater: {} in funds", self.simulation.stats.funds);
+        self.window.set_title(title);
+    }
+
+    /// Serialise the session, for `load_game` to resume later.
+    pub fn save(&mut self) -> String {
+        save_game(&mut self.simulation)
+    }
+
     fn key_command(&mut self, key: Key) {
         // @TODO: Ascertain whether these codes are macOS-specific.
         // @TODO: Restructure this around returning Option from entirety.
@@ -64,56 +96,168 @@ impl RenderToPiston {
             Key::Right => Cmd::Right,
             Key::Backspace | Key::Delete => Cmd::Delete,
             Key::Space | Key::NumPadSpace => Cmd::Pave,
-            key => match Building::from_key(key) {
-                Some(building) => Cmd::Build(building),
-                None => {
+            Key::T => Cmd::Rail,
+            Key::A => Cmd::Anchor,
+            Key::Z => Cmd::Undo,
+            Key::Y => Cmd::Redo,
+            Key::D0 => Cmd::Zone(None),
+            Key::V => Cmd::ToggleLandValue,
+            Key::Return => Cmd::Place,
+            Key::Tab => Cmd::CycleEntrance,
+            Key::Equals => Cmd::UpgradeRoad,
+            Key::Minus => Cmd::DowngradeRoad,
+            key => match (Building::from_key(key), Zone::from_key(key)) {
+                (Some(building), _) => Cmd::Build(building),
+                (None, Some(zone)) => Cmd::Zone(Some(zone)),
+                (None, None) => {
                     println!("Unhandled key input: {:?}", key);
                     return;
                 }
             },
         };
 
-        let mut map = self.map.write().unwrap();
+        // Other edits put down the building being placed.
         match cmd {
-            Cmd::Up => {
-                map.cursor.y = map.cursor.y.saturating_sub(1);
-            }
-            Cmd::Down => {
-                map.cursor.y += 1;
-            }
-            Cmd::Left => {
-                map.cursor.x = map.cursor.x.saturating_sub(1);
-            }
-            Cmd::Right => {
-                map.cursor.x += 1;
-            }
-            Cmd::Delete => {
-                let pos = map.cursor;
-                map.delete(pos);
-            }
-            Cmd::Pave => {
-                let pos = map.cursor;
-                if map.can_pave(pos) {
-                    map.pave(pos);
+            Cmd::Pave
+            | Cmd::Rail
+            | Cmd::Delete
+            | Cmd::Zone(_)
+            | Cmd::UpgradeRoad
+            | Cmd::DowngradeRoad => self.placing = None,
+            _ => {}
+        }
+
+        // Residents for a new house are sent for once the map is unlocked.
+        let mut new_house = None;
+        let result = {
+            let mut map = self.simulation.map.write().unwrap();
+            match cmd {
+                Cmd::Up => map.move_cursor(Direction::North),
+                Cmd::Down => map.move_cursor(Direction::South),
+                Cmd::Left => map.move_cursor(Direction::West),
+                Cmd::Right => map.move_cursor(Direction::East),
+                Cmd::Delete => {
+                    let pos = map.cursor;
+                    self.simulation.history.apply(&mut map, Edit::Delete(pos))
                 }
-            }
-            Cmd::Build(building) => {
-                let pos = map.cursor;
-                if map.can_build(pos) {
-                    map.build(pos, building);
-                    if building == Building::House {
-                        let decider = ResidentDecider::new(pos);
-                        let agent = Agent::new(Coord2 { x: 40, y: 2 }, Box::new(decider));
-                        self.passenger_tx.send(agent).unwrap();
+                Cmd::Pave | Cmd::Rail => {
+                    let pos = map.cursor;
+                    let edit = match (cmd, self.anchor) {
+                        (Cmd::Pave, Some(anchor)) => Edit::PaveAlong(anchor, pos),
+                        (Cmd::Pave, None) => Edit::Pave(pos),
+                        (_, Some(anchor)) => Edit::RailAlong(anchor, pos),
+                        (_, None) => Edit::Rail(pos),
+                    };
+                    let result = self.simulation.history.apply(&mut map, edit);
+                    // Carry on dragging from the end of the path.
+                    if result.is_ok() && self.anchor.is_some() {
+                        self.anchor = Some(pos);
+                    }
+                    result
+                }
+                Cmd::Anchor => {
+                    self.anchor = match self.anchor {
+                        Some(_) => None,
+                        None => Some(map.cursor),
+                    };
+                    Ok(())
+                }
+                Cmd::UpgradeRoad => {
+                    let pos = map.cursor;
+                    self.simulation
+                        .history
+                        .apply(&mut map, Edit::UpgradeRoad(pos))
+                }
+                Cmd::DowngradeRoad => {
+                    let pos = map.cursor;
+                    self.simulation
+                        .history
+                        .apply(&mut map, Edit::DowngradeRoad(pos))
+                }
+                Cmd::Build(building) if self.placing != Some(building) => {
+                    self.placing = Some(building);
+                    self.facing = None;
+                    Ok(())
+                }
+                Cmd::Build(_) | Cmd::Place => match self.placing {
+                    Some(building) => {
+                        let pos = map.cursor;
+                        let edit = Edit::Build(pos, building, self.facing);
+                        let result = self.simulation.history.apply(&mut map, edit);
+                        if result.is_ok() && building == Building::House {
+                            new_house = Some((pos, map.capacity(pos).unwrap_or(0)));
+                        }
+                        result
+                    }
+                    None => Ok(()),
+                },
+                Cmd::CycleEntrance => {
+                    if let Some(building) = self.placing {
+                        self.facing = Self::next_facing(&map, building, self.facing);
                     }
+                    Ok(())
+                }
+                Cmd::Zone(zone) => {
+                    let pos = map.cursor;
+                    map.set_zone(pos, zone)
                 }
+                Cmd::ToggleLandValue => {
+                    self.show_land_value = !self.show_land_value;
+                    Ok(())
+                }
+                Cmd::Undo => self.simulation.history.undo(&mut map).map(|edit| {
+                    if edit.is_none() {
+                        println!("Nothing to undo.");
+                    }
+                }),
+                Cmd::Redo => self.simulation.history.redo(&mut map).map(|edit| {
+                    if edit.is_none() {
+                        println!("Nothing to redo.");
+                    }
+                }),
+            }
+        };
+        if let Some((home, places)) = new_house {
+            self.simulation.moved_in(home, places);
+        }
+        if let Err(e) = result {
+            println!("Cannot do that: {}", e);
+        }
+    }
+
+    /// The side after the one `facing` chooses which `building` could face at the
+    /// cursor, in the order `Map::entrances` lists them.
+    fn next_facing(map: &Map, building: Building, facing: Option<Direction>) -> Option<Direction> {
+        let mut facings = vec![];
+        for (_, side) in map.entrances(map.cursor, building) {
+            if !facings.contains(&side) {
+                facings.push(side);
             }
         }
+        let current = map.entrance_facing(map.cursor, building, facing)?.1;
+        let index = facings.iter().position(|side| *side == current)?;
+        Some(facings[(index + 1) % facings.len()])
     }
 
     fn draw(&mut self, e: &Event) {
-        let map = self.map.read().unwrap();
-        let agent_subunit_positions = self.agents.agent_subunit_positions();
+        let map = self.simulation.map.read().unwrap();
+        let show_land_value = self.show_land_value;
+        let facing = self.facing;
+        let dragged: Vec<Coord2> = match self.anchor {
+            Some(anchor) => Path::l_shaped(anchor, map.cursor)
+                .into_iter()
+                .flat_map(|path| path.into_iter())
+                .collect(),
+            None => vec![],
+        };
+        let preview = self.placing.map(|building| {
+            (
+                map.footprint(map.cursor, building),
+                map.can_build(map.cursor, building),
+                map.entrance_facing(map.cursor, building, facing),
+            )
+        });
+        let agent_subunit_positions = self.simulation.agents.agent_subunit_positions();
 
         self.window.draw_2d(e, |c, g| {
             clear([0.95; 4], g);
@@ -121,8 +265,18 @@ impl RenderToPiston {
             for y in 0..map.height() {
                 for x in 0..map.width() {
                     let l = Coord2 { x, y };
+                    Self::draw_terrain(c, g, l, map.terrain(l));
+                    if map.is_bridge(l) {
+                        Self::draw_bridge(c, g, l);
+                    }
+                    if let Some(zone) = map.zone(l) {
+                        Self::draw_zone(c, g, l, zone);
+                    }
                     if let Some(tile) = map.get(l) {
-                        Self::draw_tile(c, g, map.clone(), l, tile);
+                        Self::draw_tile(c, g, &map, l, tile);
+                    }
+                    if show_land_value {
+                        Self::draw_land_value(c, g, l, map.land_value(l));
                     }
                 }
             }
@@ -136,10 +290,70 @@ impl RenderToPiston {
                 }
             }
 
+            for l in &dragged {
+                Self::draw_dragged(c, g, *l);
+            }
             Self::draw_cursor(c, g, map.cursor);
+            if let Some((ref footprint, fits, entrance)) = preview {
+                Self::draw_preview(c, g, footprint, fits, entrance);
+            }
         });
     }
 
+    /// A tile of the path being dragged from the anchor.
+    fn draw_dragged(c: Context, g: &mut G2d, l: Coord2) {
+        let x = l.x * PPU;
+        let y = l.y * PPU;
+        rectangle(
+            [0.0, 0.0, 0.0, 0.15],
+            [x as f64, y as f64, PPU as f64, PPU as f64],
+            c.transform,
+            g,
+        );
+    }
+
+    /// The building being placed, green where it fits and red where it does not,
+    /// with its entrance marked.
+    fn draw_preview(
+        c: Context,
+        g: &mut G2d,
+        footprint: &[Coord2],
+        fits: bool,
+        entrance: Option<(Coord2, Direction)>,
+    ) {
+        let colour = if fits {
+            [42.0 / 255.0, 201.0 / 255.0, 111.0 / 255.0, 0.5]
+        } else {
+            [220.0 / 255.0, 50.0 / 255.0, 50.0 / 255.0, 0.5]
+        };
+        for part in footprint {
+            rectangle(
+                colour,
+                [
+                    (part.x * PPU) as f64,
+                    (part.y * PPU) as f64,
+                    PPU as f64,
+                    PPU as f64,
+                ],
+                c.transform,
+                g,
+            );
+        }
+        if let Some((entryway_pos, _)) = entrance {
+            rectangle(
+                [1.0, 0.85, 0.0, 0.9],
+                [
+                    (entryway_pos.x * PPU + 2) as f64,
+                    (entryway_pos.y * PPU + 2) as f64,
+                    (PPU - 4) as f64,
+                    (PPU - 4) as f64,
+                ],
+                c.transform,
+                g,
+            );
+        }
+    }
+
     fn draw_cursor(c: Context, g: &mut G2d, cursor: Coord2) {
         let x = cursor.x * PPU;
         let y = cursor.y * PPU;
@@ -210,35 +424,140 @@ impl RenderToPiston {
         //           g);
     }
 
-    fn draw_tile(c: Context, g: &mut G2d, _: Map, l: Coord2, tile: &Tile) {
+    fn draw_terrain(c: Context, g: &mut G2d, l: Coord2, terrain: Terrain) {
+        let colour = match terrain {
+            Terrain::Grass => return,
+            Terrain::Water => [64.0 / 255.0, 140.0 / 255.0, 212.0 / 255.0, 1.0],
+            Terrain::Forest => [34.0 / 255.0, 110.0 / 255.0, 60.0 / 255.0, 0.6],
+            Terrain::Hills => [170.0 / 255.0, 140.0 / 255.0, 100.0 / 255.0, 0.6],
+        };
+        let x = l.x * PPU;
+        let y = l.y * PPU;
+        rectangle(
+            colour,
+            [x as f64, y as f64, PPU as f64, PPU as f64],
+            c.transform,
+            g,
+        );
+    }
+
+    /// A tint over zoned land, hidden once something is built there.
+    fn draw_zone(c: Context, g: &mut G2d, l: Coord2, zone: Zone) {
+        let colour = match zone {
+            Zone::Residential => [42.0 / 255.0, 201.0 / 255.0, 111.0 / 255.0, 0.25],
+            Zone::Commercial => [0.0, 162.0 / 255.0, 1.0, 0.25],
+            Zone::Industrial => [245.0 / 255.0, 180.0 / 255.0, 31.0 / 255.0, 0.25],
+        };
+        let x = l.x * PPU;
+        let y = l.y * PPU;
+        rectangle(
+            colour,
+            [x as f64, y as f64, PPU as f64, PPU as f64],
+            c.transform,
+            g,
+        );
+    }
+
+    /// A heatmap square, from red on worthless land to green on the most valuable.
+    fn draw_land_value(c: Context, g: &mut G2d, l: Coord2, value: f64) {
+        let value = value as f32;
+        let x = l.x * PPU;
+        let y = l.y * PPU;
+        rectangle(
+            [1.0 - value, value, 0.0, 0.45],
+            [x as f64, y as f64, PPU as f64, PPU as f64],
+            c.transform,
+            g,
+        );
+    }
+
+    /// A wooden deck, for roads and rails crossing water.
+    fn draw_bridge(c: Context, g: &mut G2d, l: Coord2) {
+        let x = l.x * PPU;
+        let y = l.y * PPU;
+        rectangle(
+            [120.0 / 255.0, 85.0 / 255.0, 50.0 / 255.0, 1.0],
+            [
+                (x + 1) as f64,
+                (y + 1) as f64,
+                (PPU - 2) as f64,
+                (PPU - 2) as f64,
+            ],
+            c.transform,
+            g,
+        );
+    }
+
+    fn draw_tile(c: Context, g: &mut G2d, map: &Map, l: Coord2, tile: &Tile) {
         match *tile {
-            Tile::Building(BuildingTile { ref building, .. }) => {
-                Self::draw_building(c, g, l, building)
+            // Buildings are drawn whole, from the north-west corner of their footprint.
+            Tile::Building(BuildingTile {
+                ref building,
+                origin,
+                level,
+                ..
+            }) => {
+                if l == origin {
+                    let size = Self::building_size(map, origin);
+                    Self::draw_building(c, g, l, size, building);
+                    Self::draw_level(c, g, l, level);
+                }
             }
             Tile::Entrance(EntranceTile { orientation, .. }) => {
                 Self::draw_entrance(c, g, l, orientation)
             }
-            Tile::Paving(PavingTile { .. }) => Self::draw_paving(c, g, l),
-            Tile::Rails(RailsTile { orientation, .. }) => Self::draw_rails(c, g, l, orientation),
+            Tile::Paving(PavingTile { class, .. }) => Self::draw_paving(c, g, l, class),
+            Tile::Rails(RailsTile {
+                shape, ref station, ..
+            }) => {
+                if station.is_some() {
+                    Self::draw_station(c, g, l);
+                }
+                Self::draw_rails(c, g, l, shape)
+            }
+        }
+    }
+
+    /// The width and height in tiles of the building built at `origin`.
+    fn building_size(map: &Map, origin: Coord2) -> Coord2 {
+        let parts = map.building_tiles(origin);
+        Coord2 {
+            x: parts.iter().map(|p| p.x).max().unwrap_or(origin.x) + 1 - origin.x,
+            y: parts.iter().map(|p| p.y).max().unwrap_or(origin.y) + 1 - origin.y,
         }
     }
 
-    fn draw_building(c: Context, g: &mut G2d, l: Coord2, building: &Building) {
+    fn draw_building(c: Context, g: &mut G2d, l: Coord2, size: Coord2, building: &Building) {
         (match *building {
             Building::House => Self::draw_building_house,
             Building::Saloon => Self::draw_building_saloon,
             Building::Factory => Self::draw_building_factory,
             Building::GeneralStore => Self::draw_building_general_store,
             Building::TrainStation => Self::draw_other,
-        })(c, g, l)
+        })(c, g, l, size)
+    }
+
+    /// A dark mark in the top-left corner for each level above the first.
+    fn draw_level(c: Context, g: &mut G2d, l: Coord2, level: u64) {
+        let x = l.x * PPU;
+        let y = l.y * PPU;
+        for i in 1..level {
+            rectangle(
+                [0.1, 0.1, 0.1, 1.0],
+                [(x + 1 + (i - 1) * 3) as f64, (y + 1) as f64, 2.0, 2.0],
+                c.transform,
+                g,
+            );
+        }
     }
 
-    fn draw_building_house(c: Context, g: &mut G2d, l: Coord2) {
+    fn draw_building_house(c: Context, g: &mut G2d, l: Coord2, size: Coord2) {
         let x = l.x * PPU;
         let y = l.y * PPU;
+        let (w, h) = (size.x * PPU, size.y * PPU);
         rectangle(
             [42.0 / 255.0, 201.0 / 255.0, 111.0 / 255.0, 1.0],
-            [x as f64, y as f64, PPU as f64, PPU as f64],
+            [x as f64, y as f64, w as f64, h as f64],
             c.transform,
             g,
         );
@@ -247,42 +566,45 @@ impl RenderToPiston {
             [
                 (x + 3) as f64,
                 (y + 3) as f64,
-                (PPU - 6) as f64,
-                (PPU - 6) as f64,
+                (w - 6) as f64,
+                (h - 6) as f64,
             ],
             c.transform,
             g,
         );
     }
 
-    fn draw_building_saloon(c: Context, g: &mut G2d, l: Coord2) {
+    fn draw_building_saloon(c: Context, g: &mut G2d, l: Coord2, size: Coord2) {
         let x = l.x * PPU;
         let y = l.y * PPU;
+        let (w, h) = (size.x * PPU, size.y * PPU);
         rectangle(
             [0.0 / 255.0, 162.0 / 255.0, 255.0 / 255.0, 1.0],
-            [x as f64, y as f64, PPU as f64, PPU as f64],
+            [x as f64, y as f64, w as f64, h as f64],
             c.transform,
             g,
         );
     }
 
-    fn draw_building_factory(c: Context, g: &mut G2d, l: Coord2) {
+    fn draw_building_factory(c: Context, g: &mut G2d, l: Coord2, size: Coord2) {
         let x = l.x * PPU;
         let y = l.y * PPU;
+        let (w, h) = (size.x * PPU, size.y * PPU);
         rectangle(
             [245.0 / 255.0, 51.0 / 255.0, 31.0 / 255.0, 1.0],
-            [x as f64, y as f64, PPU as f64, PPU as f64],
+            [x as f64, y as f64, w as f64, h as f64],
             c.transform,
             g,
         );
     }
 
-    fn draw_building_general_store(c: Context, g: &mut G2d, l: Coord2) {
+    fn draw_building_general_store(c: Context, g: &mut G2d, l: Coord2, size: Coord2) {
         let x = l.x * PPU;
         let y = l.y * PPU;
+        let (w, h) = (size.x * PPU, size.y * PPU);
         rectangle(
             [159.0 / 255.0, 0.0 / 255.0, 224.0 / 255.0, 1.0],
-            [x as f64, y as f64, PPU as f64, PPU as f64],
+            [x as f64, y as f64, w as f64, h as f64],
             c.transform,
             g,
         );
@@ -311,22 +633,36 @@ impl RenderToPiston {
         }
     }
 
-    fn draw_paving(c: Context, g: &mut G2d, l: Coord2) {
+    /// Footpaths are pale gravel, streets tarmac, and avenues tarmac with a centre line.
+    fn draw_paving(c: Context, g: &mut G2d, l: Coord2, class: RoadClass) {
         let x = l.x * PPU;
         let y = l.y * PPU;
+        let colour = match class {
+            RoadClass::Footpath => [0.6, 0.55, 0.45, 1.0],
+            RoadClass::Street => [0.2, 0.2, 0.2, 1.0],
+            RoadClass::Avenue => [0.1, 0.1, 0.1, 1.0],
+        };
         rectangle(
-            [0.2, 0.2, 0.2, 1.0],
+            colour,
             [x as f64, y as f64, PPU as f64, PPU as f64],
             c.transform,
             g,
         );
+        if class == RoadClass::Avenue {
+            rectangle(
+                [0.9, 0.8, 0.2, 1.0],
+                [(x + PPU / 2 - 1) as f64, (y + PPU / 2 - 1) as f64, 2.0, 2.0],
+                c.transform,
+                g,
+            );
+        }
     }
 
-    fn draw_rails(c: Context, g: &mut G2d, l: Coord2, orientation: Orientation) {
+    fn draw_rails(c: Context, g: &mut G2d, l: Coord2, shape: RailShape) {
         let x = l.x * PPU;
         let y = l.y * PPU;
-        match orientation {
-            Orientation::Vertical => {
+        match shape {
+            RailShape::Straight(Orientation::Vertical) => {
                 rectangle(
                     [0.0, 0.0, 0.0, 1.0],
                     [(x + 2) as f64, y as f64, 2.0, PPU as f64],
@@ -340,7 +676,7 @@ impl RenderToPiston {
                     g,
                 );
             }
-            Orientation::Horizontal => {
+            RailShape::Straight(Orientation::Horizontal) => {
                 for x2 in 0..(PPU - 1) {
                     if x2 % 2 == 0 {
                         rectangle(
@@ -364,17 +700,73 @@ impl RenderToPiston {
                     g,
                 );
             }
+            shape => {
+                for direction in shape.directions() {
+                    Self::draw_rails_arm(c, g, l, direction);
+                }
+            }
         }
     }
 
-    fn draw_other(c: Context, g: &mut G2d, l: Coord2) {
+    /// Draw rails running from the middle of a tile out to one side of it.
+    fn draw_rails_arm(c: Context, g: &mut G2d, l: Coord2, direction: Direction) {
+        let x = l.x * PPU;
+        let y = l.y * PPU;
+        let half = PPU / 2;
+        match direction {
+            Direction::North | Direction::South => {
+                let y = if direction == Direction::North {
+                    y
+                } else {
+                    y + half
+                };
+                for x2 in &[x + 2, x + PPU - 4] {
+                    rectangle(
+                        [0.0, 0.0, 0.0, 0.7],
+                        [*x2 as f64, y as f64, 2.0, half as f64],
+                        c.transform,
+                        g,
+                    );
+                }
+            }
+            Direction::East | Direction::West => {
+                let x = if direction == Direction::West {
+                    x
+                } else {
+                    x + half
+                };
+                for y2 in &[y + 2, y + PPU - 4] {
+                    rectangle(
+                        [0.0, 0.0, 0.0, 0.7],
+                        [x as f64, *y2 as f64, half as f64, 2.0],
+                        c.transform,
+                        g,
+                    );
+                }
+            }
+        }
+    }
+
+    fn draw_station(c: Context, g: &mut G2d, l: Coord2) {
         let x = l.x * PPU;
         let y = l.y * PPU;
         rectangle(
-            [0.2, 0.4, 0.6, 1.0],
+            [0.2, 0.4, 0.6, 0.5],
             [x as f64, y as f64, PPU as f64, PPU as f64],
             c.transform,
             g,
         );
     }
+
+    fn draw_other(c: Context, g: &mut G2d, l: Coord2, size: Coord2) {
+        let x = l.x * PPU;
+        let y = l.y * PPU;
+        let (w, h) = (size.x * PPU, size.y * PPU);
+        rectangle(
+            [0.2, 0.4, 0.6, 1.0],
+            [x as f64, y as f64, w as f64, h as f64],
+            c.transform,
+            g,
+        );
+    }
 }
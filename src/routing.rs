@@ -4,6 +4,14 @@ use std::collections::*;
 
 const COST_OF_AN_EMPTY_TILE: f64 = 30.0;
 const COST_OF_AN_EMPTY_TILE_NEXT_TO_A_BUILDING: f64 = COST_OF_AN_EMPTY_TILE * 4.0;
+/// The cost of a step through an entrance or across a building. No cheaper than
+/// along the best road, so that `heuristic_cost_estimate` stays a lower bound.
+const COST_OF_A_STEP_INDOORS: f64 = 0.5;
+/// How many buildings `closest_building` routes to before narrowing its search.
+const NEAREST_CANDIDATES: usize = 3;
+/// How far `closest_building_where` first looks, doubling each time it finds
+/// nothing, when every nearest building is turned away.
+const WIDENING_RADIUS: u64 = 16;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Route {
@@ -16,19 +24,31 @@ pub enum Route {
 ///
 /// Derived from https://en.wikipedia.org/wiki/A*_search_algorithm#Pseudocode
 pub fn route(map: &Map, start_pos: Coord2, goal_pos: Coord2) -> Route {
+    costed_route(map, start_pos, goal_pos).0
+}
+
+/// The route `route` finds, with its cost: nothing to arrive where it starts, and
+/// infinite where there is no route.
+fn costed_route(map: &Map, start_pos: Coord2, goal_pos: Coord2) -> (Route, f64) {
     if start_pos == goal_pos {
-        return Route::Complete;
+        return (Route::Complete, 0.0);
     }
-    if map.get(goal_pos).is_none() {
-        return Route::NotRouteable;
+    if map.get(goal_pos).is_none() || !map.is_bounded(start_pos) {
+        return (Route::NotRouteable, ::std::f64::INFINITY);
     }
 
-    let mut closed = HashSet::new();
-    let mut open = HashSet::new();
-    open.insert(start_pos);
-    let mut came_from = HashMap::new();
+    // Per-tile search state is kept in grids rather than hashed, as it is read
+    // for every neighbour of every tile visited.
+    let dimensions = Coord2 {
+        x: map.width(),
+        y: map.height(),
+    };
+    let mut closed = Grid::new(dimensions);
+    let mut open = Grid::new(dimensions);
+    open.insert(start_pos, ());
+    let mut came_from = Grid::new(dimensions);
 
-    let mut g_score = HashMap::new();
+    let mut g_score = Grid::new(dimensions);
     g_score.insert(start_pos, 0.0);
     // Max heap thus use negative of costs.
     let mut f_score = BinaryHeap::new();
@@ -43,20 +63,22 @@ pub fn route(map: &Map, start_pos: Coord2, goal_pos: Coord2) -> Route {
             None => unreachable!(),
         };
         if current_pos == goal_pos {
-            return Route::Tiles(reconstruct_path(came_from, current_pos));
+            let cost = *g_score.get(current_pos).unwrap();
+            return (Route::Tiles(reconstruct_path(&came_from, current_pos)), cost);
         }
 
-        open.remove(&current_pos);
-        closed.insert(current_pos);
+        open.remove(current_pos);
+        closed.insert(current_pos, ());
         let current = map.get(current_pos);
+        let current_g_score = *g_score.get(current_pos).unwrap();
         for (neighbour_pos, neighbour_move_cost) in tile_neighbours(current, current_pos, map) {
-            if closed.contains(&neighbour_pos) {
+            if closed.contains(neighbour_pos) {
                 continue;
             }
-            let tentative_g_score = g_score[&current_pos] + neighbour_move_cost;
-            if !open.contains(&neighbour_pos) {
-                open.insert(neighbour_pos);
-            } else if tentative_g_score >= g_score[&neighbour_pos] {
+            let tentative_g_score = current_g_score + neighbour_move_cost;
+            if !open.contains(neighbour_pos) {
+                open.insert(neighbour_pos, ());
+            } else if tentative_g_score >= *g_score.get(neighbour_pos).unwrap() {
                 continue;
             }
             came_from.insert(neighbour_pos, current_pos);
@@ -68,7 +90,51 @@ pub fn route(map: &Map, start_pos: Coord2, goal_pos: Coord2) -> Route {
         }
     }
 
-    Route::NotRouteable
+    (Route::NotRouteable, ::std::f64::INFINITY)
+}
+
+/// The cost of the cheapest route from any of `sources` to every tile reachable
+/// for no more than `limit`, along roads and through buildings but never over
+/// empty ground.
+///
+/// This is Dijkstra's algorithm, with the same steps as `route`.
+pub fn costs_from(map: &Map, sources: &[Coord2], limit: f64) -> Grid<f64> {
+    let dimensions = Coord2 {
+        x: map.width(),
+        y: map.height(),
+    };
+    let mut costs = Grid::new(dimensions);
+    let mut queue = BinaryHeap::new();
+    for &source in sources {
+        if map.get(source).is_some() {
+            costs.insert(source, 0.0);
+            queue.push(FScoreItem {
+                f_score: -0.0,
+                item: source,
+            });
+        }
+    }
+
+    while let Some(FScoreItem { f_score, item }) = queue.pop() {
+        let cost = -f_score;
+        if cost > *costs.get(item).unwrap() {
+            continue;
+        }
+        for (neighbour_pos, move_cost) in tile_neighbours(map.get(item), item, map) {
+            let neighbour_cost = cost + move_cost;
+            if map.get(neighbour_pos).is_none() || neighbour_cost > limit {
+                continue;
+            }
+            if costs.get(neighbour_pos).map_or(true, |&c| neighbour_cost < c) {
+                costs.insert(neighbour_pos, neighbour_cost);
+                queue.push(FScoreItem {
+                    f_score: -neighbour_cost,
+                    item: neighbour_pos,
+                });
+            }
+        }
+    }
+    costs
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -92,26 +158,93 @@ pub fn direction_of_route(map: &Map, start_pos: Coord2, goal_pos: Coord2) -> Rou
     }
 }
 
+/// A route kept between steps, so that a traveller searches once per journey
+/// rather than once per tile. It is forgotten when a `MapEvent` might change it.
+#[derive(Clone, Debug, Default)]
+pub struct RouteCache {
+    /// The tiles still to travel, ending at the goal.
+    remaining: VecDeque<Coord2>,
+}
+
+impl RouteCache {
+    pub fn new() -> RouteCache {
+        RouteCache::default()
+    }
+
+    /// As `direction_of_route`, following the cached route while it still leads on
+    /// from `start_pos` to `goal_pos`.
+    pub fn direction(&mut self, map: &Map, start_pos: Coord2, goal_pos: Coord2) -> RouteDirection {
+        while self.remaining.front() == Some(&start_pos) {
+            self.remaining.pop_front();
+        }
+        let leads_on = self.remaining.back() == Some(&goal_pos)
+            && self
+                .remaining
+                .front()
+                .map_or(false, |next| start_pos.neighbours().contains(next));
+        if !leads_on {
+            self.remaining = match route(map, start_pos, goal_pos) {
+                Route::Tiles(tiles) => tiles.into_iter().skip(1).collect(),
+                Route::Complete => return RouteDirection::Complete,
+                Route::NotRouteable => {
+                    self.clear();
+                    return RouteDirection::NotRouteable;
+                }
+            };
+        }
+        match Direction::between_coord2s(start_pos, self.remaining[0]) {
+            Some(direction) => RouteDirection::Direction(direction),
+            None => RouteDirection::NotRouteable,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.remaining.clear();
+    }
+
+    /// Forget the route if `event` might change it.
+    pub fn handle(&mut self, event: &MapEvent) {
+        let stale = match *event {
+            // These only make the way dearer, on and beside where they happen.
+            MapEvent::PavingRemoved(location) | MapEvent::RailLaid(location) => self
+                .remaining
+                .iter()
+                .any(|&tile| tile == location || tile.neighbours().contains(&location)),
+            // A building's level changes what it holds, not the way to it.
+            MapEvent::LevelChanged(..) => false,
+            // Anything else might open up a cheaper way.
+            _ => true,
+        };
+        if stale {
+            self.clear();
+        }
+    }
+}
+
 /// Find route to closest of several possible destinations.
+pub fn route_to_any(map: &Map, start_pos: Coord2, goals_pos: &[Coord2]) -> Route {
+    costed_route_to_any(map, start_pos, goals_pos).0
+}
+
+/// The route `route_to_any` finds, with its cost as `costed_route` gives it.
 ///
-/// This could be optimised with an adaptation of A* search.
-pub fn route_to_any(map: &Map, start_pos: Coord2, goals_pos: &Vec<Coord2>) -> Route {
-    let mut routes = vec![];
+/// Goals are tried nearest first in a straight line, stopping once none left could
+/// have a cheaper route than the best so far.
+fn costed_route_to_any(map: &Map, start_pos: Coord2, goals_pos: &[Coord2]) -> (Route, f64) {
+    let mut goals_pos = goals_pos.to_vec();
+    goals_pos.sort_by_key(|goal_pos| steps_between(start_pos, *goal_pos));
+    let mut best = (Route::NotRouteable, ::std::f64::INFINITY);
 
     for goal_pos in goals_pos {
-        match route(map, start_pos, *goal_pos) {
-            Route::NotRouteable => {}
-            Route::Complete => return Route::Complete,
-            Route::Tiles(route) => {
-                routes.push(route);
-            }
+        if best.1 <= heuristic_cost_estimate(start_pos, goal_pos) {
+            break;
+        }
+        let (route, cost) = costed_route(map, start_pos, goal_pos);
+        if cost < best.1 {
+            best = (route, cost);
         }
     }
-
-    match routes.into_iter().min_by(|x, y| x.len().cmp(&y.len())) {
-        Some(route) => Route::Tiles(route),
-        None => Route::NotRouteable,
-    }
+    best
 }
 
 pub fn route_to_building(map: &Map, start_pos: Coord2, building: Building) -> Route {
@@ -122,13 +255,68 @@ pub fn route_to_building(map: &Map, start_pos: Coord2, building: Building) -> Ro
     }
 }
 
+/// The `building` with the cheapest route from `start_pos`.
 pub fn closest_building(map: &Map, start_pos: Coord2, building: Building) -> Option<Coord2> {
-    match route_to_building(map, start_pos, building) {
+    closest_building_where(map, start_pos, building, |_| true)
+}
+
+/// The `building` accepted by `admits` with the cheapest route from `start_pos`.
+/// Only those nearest in a straight line are routed to at first, as they are likely
+/// closest by road too, looking further out while all of those are turned away, and
+/// then only those near enough to have a cheaper route.
+pub fn closest_building_where<F>(
+    map: &Map,
+    start_pos: Coord2,
+    building: Building,
+    admits: F,
+) -> Option<Coord2>
+where
+    F: Fn(Coord2) -> bool,
+{
+    let admitted = |origins: Vec<Coord2>| -> Vec<Coord2> {
+        origins.into_iter().filter(|o| admits(*o)).collect()
+    };
+    let mut nearest = admitted(map.nearest_buildings(start_pos, building, NEAREST_CANDIDATES));
+    let mut radius = WIDENING_RADIUS;
+    while nearest.is_empty() && radius < 2 * (map.width() + map.height()) {
+        nearest = admitted(map.buildings_within(start_pos, radius, building));
+        nearest.truncate(NEAREST_CANDIDATES);
+        radius *= 2;
+    }
+    let best = match costed_route_to_any(map, start_pos, &nearest) {
+        (Route::Complete, _) => return Some(start_pos),
+        best => best,
+    };
+    // A cheaper route can take no more steps than this, and so cannot start
+    // further away in a straight line.
+    let others = if best.1.is_finite() {
+        let radius = (best.1 / cheapest_step()).ceil() as u64;
+        map.buildings_within(start_pos, radius, building)
+    } else {
+        map.buildings.get(&building).cloned().unwrap_or_default()
+    };
+    let others: Vec<Coord2> = admitted(others)
+        .into_iter()
+        .filter(|o| !nearest.contains(o))
+        .collect();
+    let best = match costed_route_to_any(map, start_pos, &others) {
+        other if other.1 < best.1 => other,
+        _ => best,
+    };
+    match best.0 {
         Route::Tiles(route) => route.last().cloned(),
-        _ => None,
+        Route::Complete => Some(start_pos),
+        Route::NotRouteable => None,
     }
 }
 
+/// Straight-line distance in steps along rows and columns.
+fn steps_between(a: Coord2, b: Coord2) -> u64 {
+    let x_diff = max(a.x, b.x) - min(a.x, b.x);
+    let y_diff = max(a.y, b.y) - min(a.y, b.y);
+    x_diff + y_diff
+}
+
 #[derive(Clone, Debug, PartialEq)]
 struct FScoreItem<T>
 where
@@ -164,76 +352,182 @@ where
 
 fn tile_neighbours(tile: Option<&Tile>, location: Coord2, map: &Map) -> Vec<(Coord2, f64)> {
     match tile.clone() {
-        Some(Tile::Building(BuildingTile {
-            ref entryway_pos, ..
-        })) => vec![(*entryway_pos, 0.1)],
+        Some(Tile::Building(ref building_tile)) => {
+            building_neighbours(building_tile, location, map)
+        }
         Some(Tile::Entrance(EntranceTile {
             ref road_pos,
             ref building_pos,
             ..
-        })) => vec![(*road_pos, 0.1), (*building_pos, 0.1)],
+        })) => vec![
+            (*road_pos, COST_OF_A_STEP_INDOORS),
+            (*building_pos, COST_OF_A_STEP_INDOORS),
+        ],
         Some(Tile::Paving(PavingTile {
-            ref entryways_pos,
-            ref pavings_pos,
+            entryways, pavings, ..
         })) => {
             // This is inherently opinionated. We prioritise local entryways
             // over other pavings.
-            let mut neighbours_with_costs: Vec<(Coord2, f64)> = entryways_pos
-                .iter()
-                .cloned()
-                .map(|e| (e, 0.1))
-                .chain(pavings_pos.iter().map(|p| (*p, 1.0)))
+            let mut neighbours_with_costs: Vec<(Coord2, f64)> = entryways
+                .locations(location)
+                .into_iter()
+                .map(|e| (e, COST_OF_A_STEP_INDOORS))
+                .chain(
+                    pavings
+                        .locations(location)
+                        .into_iter()
+                        .map(|p| (p, road_cost(map, p))),
+                )
                 .collect();
             // To maintain entryway-before-paving priority order, we add empty
             // tiles in a second pass.
-            let mut neighbours: HashSet<Coord2> = neighbours_with_costs
-                .clone()
-                .into_iter()
-                .map(|(location, _)| location)
+            let mut neighbours: Vec<Coord2> = neighbours_with_costs
+                .iter()
+                .map(|&(location, _)| location)
                 .collect();
             for neighbour in location.neighbours() {
                 if !neighbours.contains(&neighbour) && map.can_walk(neighbour) {
-                    let cost = if map.can_pave(neighbour) {
-                        COST_OF_AN_EMPTY_TILE
-                    } else {
-                        COST_OF_AN_EMPTY_TILE_NEXT_TO_A_BUILDING
-                    };
-                    neighbours.insert(neighbour);
-                    neighbours_with_costs.push((neighbour, cost));
+                    neighbours.push(neighbour);
+                    neighbours_with_costs.push((neighbour, cost_to_walk_onto(map, neighbour)));
                 }
             }
             neighbours_with_costs
         }
-        // We don't route things along railways. Yet.
+        // Stations are entered like buildings. We don't route things along
+        // railways. Yet.
+        Some(Tile::Rails(RailsTile {
+            station: Some(ref building_tile),
+            ..
+        })) => building_neighbours(building_tile, location, map),
         Some(Tile::Rails(RailsTile { .. })) => vec![],
         None => location
             .neighbours()
             .into_iter()
             .filter(|neighbour| map.can_walk(*neighbour))
-            .map(|neighbour| {
-                let cost = if map.can_pave(neighbour) {
-                    COST_OF_AN_EMPTY_TILE
-                } else {
-                    COST_OF_AN_EMPTY_TILE_NEXT_TO_A_BUILDING
-                };
-                (neighbour, cost)
-            })
+            .map(|neighbour| (neighbour, cost_to_walk_onto(map, neighbour)))
             .collect(),
     }
 }
 
-fn reconstruct_path(came_from: HashMap<Coord2, Coord2>, mut current_pos: Coord2) -> Vec<Coord2> {
+/// The cost of stepping onto `location` off-road, where it is walkable ground or
+/// paving.
+fn cost_to_walk_onto(map: &Map, location: Coord2) -> f64 {
+    let cost = if !map.neighbours_a_building(location) {
+        COST_OF_AN_EMPTY_TILE
+    } else {
+        COST_OF_AN_EMPTY_TILE_NEXT_TO_A_BUILDING
+    };
+    // Paving carries walkers over any ground, bridges included.
+    match map.get(location) {
+        Some(_) => cost,
+        None => cost * map.terrain(location).walking_cost(),
+    }
+}
+
+/// The cost of stepping along a road onto the paving at `location`, by its class.
+fn road_cost(map: &Map, location: Coord2) -> f64 {
+    map.get(location)
+        .and_then(Tile::as_paving)
+        .map_or(1.0, |paving| paving.class.cost())
+}
+
+/// Buildings are crossed tile by tile, and left through their entrance.
+fn building_neighbours(
+    building_tile: &BuildingTile,
+    location: Coord2,
+    map: &Map,
+) -> Vec<(Coord2, f64)> {
+    location
+        .neighbours()
+        .into_iter()
+        .filter(|neighbour| {
+            *neighbour == building_tile.entryway_pos
+                || map.building_at(*neighbour).map(|b| b.origin) == Some(building_tile.origin)
+        })
+        .map(|neighbour| (neighbour, COST_OF_A_STEP_INDOORS))
+        .collect()
+}
+
+fn reconstruct_path(came_from: &Grid<Coord2>, mut current_pos: Coord2) -> Vec<Coord2> {
     let mut path = vec![current_pos];
-    while came_from.contains_key(&current_pos) {
-        current_pos = came_from[&current_pos];
+    while let Some(&previous_pos) = came_from.get(current_pos) {
+        current_pos = previous_pos;
         path.push(current_pos);
     }
     path.reverse();
     path
 }
 
+/// The cost of the cheapest kind of step.
+fn cheapest_step() -> f64 {
+    RoadClass::all()
+        .iter()
+        .map(RoadClass::cost)
+        .fold(COST_OF_A_STEP_INDOORS, f64::min)
+}
+
 fn heuristic_cost_estimate(current_pos: Coord2, goal_pos: Coord2) -> f64 {
-    let x_diff = max(goal_pos.x, current_pos.x) - min(goal_pos.x, current_pos.x);
-    let y_diff = max(goal_pos.y, current_pos.y) - min(goal_pos.y, current_pos.y);
-    (x_diff + y_diff) as f64
+    // Every step costs at least as much as the cheapest kind of step, so this
+    // never overestimates.
+    steps_between(current_pos, goal_pos) as f64 * cheapest_step()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_prefer_better_roads() {
+        let mut map = Map::new(Coord2 { x: 12, y: 8 });
+        let direct: Vec<Coord2> = (7..11).map(|x| Coord2 { x, y: 2 }).collect();
+        let detour: Vec<Coord2> = vec![Coord2 { x: 6, y: 3 }, Coord2 { x: 6, y: 4 }]
+            .into_iter()
+            .chain((7..11).map(|x| Coord2 { x, y: 4 }))
+            .chain(vec![Coord2 { x: 10, y: 3 }])
+            .collect();
+        for &location in direct.iter().chain(detour.iter()) {
+            map.pave(location).unwrap();
+        }
+        let (start, goal) = (Coord2 { x: 6, y: 2 }, Coord2 { x: 10, y: 2 });
+        assert_eq!(
+            route(&map, start, goal),
+            Route::Tiles(vec![start].into_iter().chain(direct.clone()).collect())
+        );
+
+        for &location in &direct[..3] {
+            map.downgrade_road(location).unwrap();
+        }
+        for &location in &detour {
+            map.upgrade_road(location).unwrap();
+        }
+        match route(&map, start, goal) {
+            Route::Tiles(tiles) => assert!(tiles.contains(&Coord2 { x: 8, y: 4 })),
+            other => panic!("expected a route, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn the_cheapest_route_wins_over_the_shortest() {
+        let mut map = Map::new(Coord2 { x: 12, y: 8 });
+        for x in (1..6).chain(7..11) {
+            map.pave(Coord2 { x, y: 2 }).unwrap();
+        }
+        for x in 1..6 {
+            map.upgrade_road(Coord2 { x, y: 2 }).unwrap();
+        }
+        for x in 7..11 {
+            map.downgrade_road(Coord2 { x, y: 2 }).unwrap();
+        }
+        let (start, avenue_end, footpath_end) = (
+            Coord2 { x: 6, y: 2 },
+            Coord2 { x: 1, y: 2 },
+            Coord2 { x: 10, y: 2 },
+        );
+        let (route, cost) = costed_route_to_any(&map, start, &[footpath_end, avenue_end]);
+        assert_eq!(cost, 5.0 * RoadClass::Avenue.cost());
+        match route {
+            Route::Tiles(tiles) => assert_eq!(tiles.last(), Some(&avenue_end)),
+            other => panic!("expected a route, got {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,248 @@
+use super::*;
+use std::collections::*;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::mpsc;
+
+/// Version written by `save_game`. Bump whenever the layout of saves changes
+/// incompatibly.
+pub const SAVE_FORMAT_VERSION: u64 = 1;
+
+const SAVE_FORMAT_HEADER: &str = "tilewater-save";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SaveError {
+    /// The header named a format version this build does not understand.
+    UnsupportedVersion(String),
+    /// A record was malformed, or a different record was expected.
+    BadRecord { line: usize, record: String },
+    /// No loader is registered for a decider tag.
+    UnknownDecider { line: usize, tag: String },
+    /// The file ended before all records were read.
+    UnexpectedEnd,
+    /// The embedded map did not parse.
+    Map(ParseMapError),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SaveError::UnsupportedVersion(ref version) => {
+                write!(f, "unsupported save format version '{}'", version)
+            }
+            SaveError::BadRecord { line, ref record } => {
+                write!(f, "line {}: malformed record '{}'", line, record)
+            }
+            SaveError::UnknownDecider { line, ref tag } => {
+                write!(f, "line {}: no decider registered as '{}'", line, tag)
+            }
+            SaveError::UnexpectedEnd => write!(f, "save ended unexpectedly"),
+            SaveError::Map(ref e) => write!(f, "map: {}", e),
+        }
+    }
+}
+
+impl Error for SaveError {
+    fn description(&self) -> &str {
+        "invalid save game"
+    }
+}
+
+impl From<ParseMapError> for SaveError {
+    fn from(e: ParseMapError) -> SaveError {
+        SaveError::Map(e)
+    }
+}
+
+/// Serialise the whole session: the map, every agent and every decider's state,
+/// and the progress of growth, levels and income.
+pub fn save_game(simulation: &mut Simulation) -> String {
+    let mut writer = SaveWriter::new();
+    writer.record(&[SAVE_FORMAT_HEADER, &SAVE_FORMAT_VERSION.to_string()]);
+    simulation.save(&mut writer);
+    writer.finish()
+}
+
+/// Restore a session written by `save_game`.
+///
+/// Trains are handed `passenger_rx`, and new residents are sent on `passenger_tx`,
+/// so that they still arrive by train after loading.
+pub fn load_game(
+    text: &str,
+    registry: &DeciderRegistry,
+    passenger_tx: mpsc::Sender<Agent>,
+    passenger_rx: mpsc::Receiver<Agent>,
+) -> Result<Simulation, SaveError> {
+    let mut reader = SaveReader::new(text, registry, passenger_rx);
+    let (line, header) = reader.next_record()?;
+    if header.len() != 2 || header[0] != SAVE_FORMAT_HEADER {
+        return Err(reader.bad_record(line));
+    }
+    if header[1] != SAVE_FORMAT_VERSION.to_string() {
+        return Err(SaveError::UnsupportedVersion(header[1].to_string()));
+    }
+    Simulation::load(&mut reader, passenger_tx)
+}
+
+/// Loads one kind of decider from the fields after the tag in its `decider` record,
+/// which is on `line`, reading any further records it wrote.
+pub type DeciderLoader = fn(
+    line: usize,
+    fields: &[&str],
+    reader: &mut SaveReader,
+) -> Result<Box<Decider + Send + Sync>, SaveError>;
+
+/// Maps the tag each `Decider` reports to the function which loads it back, since
+/// a `Box<Decider>` cannot otherwise be told apart once saved.
+pub struct DeciderRegistry {
+    loaders: HashMap<&'static str, DeciderLoader>,
+}
+
+impl DeciderRegistry {
+    pub fn new() -> DeciderRegistry {
+        DeciderRegistry {
+            loaders: HashMap::new(),
+        }
+    }
+
+    pub fn register(&mut self, tag: &'static str, loader: DeciderLoader) {
+        self.loaders.insert(tag, loader);
+    }
+
+    pub fn get(&self, tag: &str) -> Option<DeciderLoader> {
+        self.loaders.get(tag).cloned()
+    }
+}
+
+impl Default for DeciderRegistry {
+    /// A registry of every decider in this crate.
+    fn default() -> DeciderRegistry {
+        let mut registry = DeciderRegistry::new();
+        registry.register(ResidentDecider::TAG, ResidentDecider::load);
+        registry.register(TrainDecider::TAG, TrainDecider::load);
+        registry
+    }
+}
+
+/// Accumulates a save as whitespace-separated records, one per line.
+pub struct SaveWriter {
+    text: String,
+}
+
+impl SaveWriter {
+    pub fn new() -> SaveWriter {
+        SaveWriter {
+            text: String::new(),
+        }
+    }
+
+    pub fn record<T: AsRef<str>>(&mut self, fields: &[T]) {
+        let fields: Vec<&str> = fields.iter().map(|f| f.as_ref()).collect();
+        self.text.push_str(&fields.join(" "));
+        self.text.push('\n');
+    }
+
+    /// Append `text` as it is, for `SaveReader::rest` to read back whole.
+    pub fn text(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    pub fn finish(self) -> String {
+        self.text
+    }
+}
+
+/// Reads back the records written by a `SaveWriter`.
+pub struct SaveReader<'a> {
+    lines: Vec<&'a str>,
+    next_line: usize,
+    registry: &'a DeciderRegistry,
+    passenger_rx: Option<mpsc::Receiver<Agent>>,
+}
+
+impl<'a> SaveReader<'a> {
+    pub fn new(
+        text: &'a str,
+        registry: &'a DeciderRegistry,
+        passenger_rx: mpsc::Receiver<Agent>,
+    ) -> SaveReader<'a> {
+        SaveReader {
+            lines: text.lines().collect(),
+            next_line: 0,
+            registry,
+            passenger_rx: Some(passenger_rx),
+        }
+    }
+
+    /// The next non-blank record with its 1-based line number.
+    pub fn next_record(&mut self) -> Result<(usize, Vec<&'a str>), SaveError> {
+        while self.next_line < self.lines.len() {
+            let line = self.lines[self.next_line];
+            self.next_line += 1;
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if !fields.is_empty() {
+                return Ok((self.next_line, fields));
+            }
+        }
+        Err(SaveError::UnexpectedEnd)
+    }
+
+    /// The next record, which must begin with `keyword`. Returns the fields after it.
+    pub fn expect(&mut self, keyword: &str) -> Result<(usize, Vec<&'a str>), SaveError> {
+        let (line, fields) = self.next_record()?;
+        if fields[0] != keyword {
+            return Err(self.bad_record(line));
+        }
+        Ok((line, fields[1..].to_vec()))
+    }
+
+    /// An error pointing at `line`.
+    pub fn bad_record(&self, line: usize) -> SaveError {
+        SaveError::BadRecord {
+            line,
+            record: self.lines.get(line - 1).unwrap_or(&"").to_string(),
+        }
+    }
+
+    /// Parse the field at `index` of the record on `line`.
+    pub fn parse<T: FromStr>(
+        &self,
+        line: usize,
+        fields: &[&str],
+        index: usize,
+    ) -> Result<T, SaveError> {
+        fields
+            .get(index)
+            .and_then(|f| f.parse().ok())
+            .ok_or_else(|| self.bad_record(line))
+    }
+
+    /// Load a decider from the `decider` record at the reader's position.
+    pub fn decider(&mut self) -> Result<Box<Decider + Send + Sync>, SaveError> {
+        let (line, fields) = self.expect("decider")?;
+        let tag = *fields.first().ok_or_else(|| self.bad_record(line))?;
+        let loader = self
+            .registry
+            .get(tag)
+            .ok_or_else(|| SaveError::UnknownDecider {
+                line,
+                tag: tag.to_string(),
+            })?;
+        loader(line, &fields[1..], self)
+    }
+
+    /// The passenger receiver to give a loaded train. Only the first train can be
+    /// handed the receiver; any others get one whose sender is already gone.
+    pub fn take_passenger_rx(&mut self) -> mpsc::Receiver<Agent> {
+        self.passenger_rx
+            .take()
+            .unwrap_or_else(|| mpsc::channel().1)
+    }
+
+    /// Everything after the current record, unparsed.
+    pub fn rest(&self) -> String {
+        let mut rest = self.lines[self.next_line..].join("\n");
+        rest.push('\n');
+        rest
+    }
+}
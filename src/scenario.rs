@@ -0,0 +1,376 @@
+use super::*;
+use std::error::Error;
+use std::str::FromStr;
+use std::sync::mpsc;
+
+/// Version read by `Scenario::from_str`. Bump whenever the records change
+/// incompatibly.
+pub const SCENARIO_FORMAT_VERSION: u64 = 1;
+
+const SCENARIO_FORMAT_HEADER: &str = "tilewater-scenario";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScenarioError {
+    /// The header named a format version this build does not understand.
+    UnsupportedVersion(String),
+    /// A record was malformed, unknown, or came before the `dimensions` it needs.
+    BadRecord { line: usize, record: String },
+    /// A record could not be carried out on the map.
+    Map { line: usize, error: MapError },
+    /// There are houses, but no `spawn` record or station for their residents to
+    /// set off from.
+    NoSpawn,
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ScenarioError::UnsupportedVersion(ref version) => {
+                write!(f, "unsupported scenario format version '{}'", version)
+            }
+            ScenarioError::BadRecord { line, ref record } => {
+                write!(f, "line {}: malformed record '{}'", line, record)
+            }
+            ScenarioError::Map { line, ref error } => write!(f, "line {}: {}", line, error),
+            ScenarioError::NoSpawn => write!(f, "houses need a spawn record or a station"),
+        }
+    }
+}
+
+impl Error for ScenarioError {
+    fn description(&self) -> &str {
+        "invalid scenario"
+    }
+}
+
+/// Why a single record could not be applied, before it is pinned to its line.
+enum RecordError {
+    Malformed,
+    Map(MapError),
+}
+
+impl From<MapError> for RecordError {
+    fn from(e: MapError) -> RecordError {
+        RecordError::Map(e)
+    }
+}
+
+/// A starting city described in a text file, one whitespace-separated record per
+/// line. After a `tilewater-scenario 1` header come, in order of use:
+///
+/// * `seed SEED` and `generate LAYOUT`, to start from a generated town.
+/// * `dimensions W H`, before anything placed on the map, each no more than
+///   `MAX_MAP_DIMENSION`.
+/// * `terrain NAME X1 Y1 X2 Y2` and `zone NAME X1 Y1 X2 Y2`, over rectangles.
+/// * `rails X Y X Y ...` and `road CLASS X Y X Y ...`, along straight segments.
+/// * `building CODE X Y [FACING]` and `station X Y`.
+/// * `spawn X Y` and `train X Y`, where residents and the train set off from.
+///
+/// Lines starting with `#` are comments.
+#[derive(Debug)]
+pub struct Scenario {
+    pub seed: u64,
+    pub map: Map,
+    /// Where residents of the houses set off from. Without one they step off at
+    /// the first station.
+    pub spawn: Option<Coord2>,
+    /// Where the train starts, if there is one.
+    pub train: Option<Coord2>,
+}
+
+impl Scenario {
+    /// Start the scenario, with a household setting off for each house. Residents
+    /// arrive by train if there is one. Returns where later households should set
+    /// off from too, for `Simulation.spawn`.
+    pub fn start(
+        self,
+        mut agents: Agents,
+        passenger_tx: &mpsc::Sender<Agent>,
+        passenger_rx: mpsc::Receiver<Agent>,
+    ) -> (Map, Agents, Option<Coord2>) {
+        let spawn = self.spawn.or_else(|| station_road(&self.map));
+        if let Some(spawn) = spawn {
+            for &house in self.map.buildings.get(&Building::House).unwrap_or(&vec![]) {
+                for agent in ResidentDecider::household(&self.map, house, spawn) {
+                    if self.train.is_some() {
+                        passenger_tx.send(agent).unwrap();
+                    } else {
+                        agents.insert(AgentKind::Resident, agent);
+                    }
+                }
+            }
+        }
+        if let Some(train) = self.train {
+            let train_decider = TrainDecider::new(passenger_rx);
+            agents.insert(AgentKind::Train, Agent::new(train, Box::new(train_decider)));
+        }
+        (self.map, agents, spawn)
+    }
+}
+
+impl FromStr for Scenario {
+    type Err = ScenarioError;
+
+    fn from_str(s: &str) -> Result<Scenario, ScenarioError> {
+        let mut records = s
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line, line.split_whitespace().collect::<Vec<_>>()))
+            .filter(|(_, _, fields)| !fields.is_empty() && !fields[0].starts_with('#'));
+
+        match records.next() {
+            Some((_, _, ref header))
+                if header.len() == 2 && header[0] == SCENARIO_FORMAT_HEADER =>
+            {
+                if header[1] != SCENARIO_FORMAT_VERSION.to_string() {
+                    return Err(ScenarioError::UnsupportedVersion(header[1].to_string()));
+                }
+            }
+            _ => {
+                return Err(ScenarioError::BadRecord {
+                    line: 1,
+                    record: "missing header".to_string(),
+                })
+            }
+        }
+
+        let mut seed = 0;
+        let mut map: Option<Map> = None;
+        let mut spawn = None;
+        let mut train = None;
+        for (line, record, fields) in records {
+            let bad_record = || ScenarioError::BadRecord {
+                line,
+                record: record.to_string(),
+            };
+            let map_error = |error| ScenarioError::Map { line, error };
+            match (fields[0], &fields[1..]) {
+                ("seed", &[value]) => seed = value.parse().map_err(|_| bad_record())?,
+                ("dimensions", args) if map.is_none() => {
+                    let dimensions = parse_coord2(args).ok_or_else(bad_record)?;
+                    Map::check_dimensions(dimensions).map_err(map_error)?;
+                    map = Some(Map::empty(dimensions));
+                }
+                ("generate", &[layout]) => {
+                    let dimensions = match map {
+                        Some(ref m) if m.tiles().next().is_none() => Coord2 {
+                            x: m.width(),
+                            y: m.height(),
+                        },
+                        _ => return Err(bad_record()),
+                    };
+                    let settings = TownSettings {
+                        seed,
+                        dimensions,
+                        layout: StreetLayout::from_name(layout).ok_or_else(bad_record)?,
+                        ..TownSettings::default()
+                    };
+                    map = Some(settings.generate_map().map_err(map_error)?);
+                }
+                ("spawn", args) => {
+                    let location = parse_coord2(args).ok_or_else(bad_record)?;
+                    let map = map.as_ref().ok_or_else(bad_record)?;
+                    if !map.is_bounded(location) {
+                        return Err(map_error(MapError::OutOfBounds(location)));
+                    }
+                    let terrain = map.terrain(location);
+                    match map.get(location) {
+                        Some(tile) if tile.as_paving().is_none() => {
+                            return Err(map_error(MapError::Occupied(location)))
+                        }
+                        None if !terrain.is_walkable() => {
+                            return Err(map_error(MapError::UnsuitableTerrain(location, terrain)))
+                        }
+                        _ => {}
+                    }
+                    spawn = Some(location);
+                }
+                ("train", args) if train.is_none() => {
+                    let location = parse_coord2(args).ok_or_else(bad_record)?;
+                    let map = map.as_ref().ok_or_else(bad_record)?;
+                    if map.get(location).and_then(Tile::as_rails).is_none() {
+                        return Err(map_error(MapError::NotOnRails(location)));
+                    }
+                    train = Some(location);
+                }
+                (keyword, args) => {
+                    let map = map.as_mut().ok_or_else(bad_record)?;
+                    match apply(map, keyword, args) {
+                        Ok(()) => {}
+                        Err(RecordError::Malformed) => return Err(bad_record()),
+                        Err(RecordError::Map(error)) => return Err(map_error(error)),
+                    }
+                }
+            }
+        }
+
+        let map = map.ok_or_else(|| ScenarioError::BadRecord {
+            line: s.lines().count(),
+            record: "missing dimensions".to_string(),
+        })?;
+        let has_houses = map
+            .buildings
+            .get(&Building::House)
+            .map_or(false, |h| !h.is_empty());
+        if has_houses && spawn.is_none() && station_road(&map).is_none() {
+            return Err(ScenarioError::NoSpawn);
+        }
+        Ok(Scenario {
+            seed,
+            map,
+            spawn,
+            train,
+        })
+    }
+}
+
+/// Carry out a record which changes the map.
+fn apply(map: &mut Map, keyword: &str, args: &[&str]) -> Result<(), RecordError> {
+    match keyword {
+        "terrain" => {
+            let terrain = args.first().and_then(|n| Terrain::from_name(n));
+            let terrain = terrain.ok_or(RecordError::Malformed)?;
+            for location in parse_rectangle(map, &args[1..])? {
+                map.set_terrain(location, terrain)?;
+            }
+        }
+        "zone" => {
+            let zone = args.first().and_then(|n| Zone::from_name(n));
+            let zone = zone.ok_or(RecordError::Malformed)?;
+            // Rectangles of zoning can take in roads and buildings, which stay unzoned.
+            for location in parse_rectangle(map, &args[1..])? {
+                if map.can_zone(location) {
+                    map.set_zone(location, Some(zone))?;
+                }
+            }
+        }
+        "rails" => map.rail_paths(&parse_paths(args)?)?,
+        "road" => {
+            let class = args.first().and_then(|n| RoadClass::from_name(n));
+            let class = class.ok_or(RecordError::Malformed)?;
+            let paths = parse_paths(&args[1..])?;
+            // Only the newly laid part takes the class, leaving any roads it joins.
+            let mut laid = vec![];
+            for path in &paths {
+                laid.extend(path.into_iter().filter(|l| map.get(*l).is_none()));
+            }
+            map.pave_paths(&paths)?;
+            for location in laid {
+                map.set_road_class(location, class)?;
+            }
+        }
+        "building" if args.len() == 3 || args.len() == 4 => {
+            let building = args[0].chars().next().and_then(Building::from_code);
+            let building = building
+                .filter(|_| args[0].len() == 1)
+                .ok_or(RecordError::Malformed)?;
+            let location = parse_coord2(&args[1..3]).ok_or(RecordError::Malformed)?;
+            let facing = match args.get(3) {
+                Some(name) => Some(Direction::from_name(name).ok_or(RecordError::Malformed)?),
+                None => None,
+            };
+            map.build_facing(location, building, facing)?;
+        }
+        "station" => {
+            let location = parse_coord2(args).ok_or(RecordError::Malformed)?;
+            map.build(location, Building::TrainStation)?;
+        }
+        _ => return Err(RecordError::Malformed),
+    }
+    Ok(())
+}
+
+fn parse_coord2(fields: &[&str]) -> Option<Coord2> {
+    if fields.len() != 2 {
+        return None;
+    }
+    let x = fields[0].parse().ok()?;
+    let y = fields[1].parse().ok()?;
+    Some(Coord2 { x, y })
+}
+
+/// Straight paths joining each of at least two points to the next.
+fn parse_paths(fields: &[&str]) -> Result<Vec<Path>, RecordError> {
+    if fields.len() < 4 || fields.len() % 2 != 0 {
+        return Err(RecordError::Malformed);
+    }
+    let points: Option<Vec<Coord2>> = fields.chunks(2).map(parse_coord2).collect();
+    points
+        .and_then(|points| Path::polyline(&points))
+        .ok_or(RecordError::Malformed)
+}
+
+/// Every location in the rectangle between two opposite corners, both of which
+/// must be on `map`.
+fn parse_rectangle(map: &Map, fields: &[&str]) -> Result<Vec<Coord2>, RecordError> {
+    if fields.len() != 4 {
+        return Err(RecordError::Malformed);
+    }
+    let a = parse_coord2(&fields[..2]).ok_or(RecordError::Malformed)?;
+    let b = parse_coord2(&fields[2..]).ok_or(RecordError::Malformed)?;
+    for &corner in &[a, b] {
+        if !map.is_bounded(corner) {
+            return Err(MapError::OutOfBounds(corner).into());
+        }
+    }
+    let mut locations = vec![];
+    for y in a.y.min(b.y)..a.y.max(b.y) + 1 {
+        for x in a.x.min(b.x)..a.x.max(b.x) + 1 {
+            locations.push(Coord2 { x, y });
+        }
+    }
+    Ok(locations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenarios_build_their_city_or_name_the_failing_line() {
+        let scenario: Scenario = include_str!("../scenarios/old-town.scenario")
+            .parse()
+            .unwrap();
+        assert!(scenario.map.validate().is_empty());
+        assert_eq!(scenario.map.buildings[&Building::House].len(), 25);
+        assert_eq!(scenario.train, Some(Coord2 { x: 0, y: 0 }));
+
+        let text =
+            "tilewater-scenario 1\n\ndimensions 10 10\nroad street 1 1 8 1\nbuilding h 3 5\n";
+        assert_eq!(
+            text.parse::<Scenario>().unwrap_err(),
+            ScenarioError::Map {
+                line: 5,
+                error: MapError::NoAdjacentRoad(Coord2 { x: 3, y: 5 }),
+            }
+        );
+
+        // Corners far off the map are refused before anything is laid out.
+        let far = ::std::u64::MAX;
+        let text = format!("tilewater-scenario 1\ndimensions 10 10\nterrain water 0 0 {} 0\n", far);
+        assert_eq!(
+            text.parse::<Scenario>().unwrap_err(),
+            ScenarioError::Map {
+                line: 3,
+                error: MapError::OutOfBounds(Coord2 { x: far, y: 0 }),
+            }
+        );
+        let text = format!("tilewater-scenario 1\ndimensions {} 1\n", far);
+        assert_eq!(
+            text.parse::<Scenario>().unwrap_err(),
+            ScenarioError::Map {
+                line: 2,
+                error: MapError::TooLarge(Coord2 { x: far, y: 1 }),
+            }
+        );
+    }
+
+    #[test]
+    fn residents_moving_in_later_set_off_from_the_spawn() {
+        let text = "tilewater-scenario 1\ndimensions 20 20\nroad street 10 3 10 9\nspawn 10 9\n";
+        let scenario: Scenario = text.parse().unwrap();
+        let (passenger_tx, passenger_rx) = mpsc::channel();
+        let (_, _, spawn) = scenario.start(Agents::new(8, 0), &passenger_tx, passenger_rx);
+        assert_eq!(spawn, Some(Coord2 { x: 10, y: 9 }));
+    }
+}
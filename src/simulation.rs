@@ -0,0 +1,325 @@
+use super::*;
+use std::collections::BTreeMap;
+use std::sync::{mpsc, Arc, RwLock};
+
+/// How many edits can be undone.
+const HISTORY_LIMIT: usize = 100;
+/// How many updates between attempts to grow a building on zoned land.
+const GROWTH_TICKS: u64 = 80;
+/// How many updates between surveys of land value.
+const LAND_VALUE_TICKS: u64 = 40;
+/// How many updates between reviews of building levels.
+const LEVEL_TICKS: u64 = 200;
+/// How many updates between collections of income.
+const INCOME_TICKS: u64 = 400;
+
+/// Everything which happens to the city from one update to the next, with or
+/// without a window to show it in.
+pub struct Simulation {
+    pub agents: Agents,
+    pub map: Arc<RwLock<Map>>,
+    /// The player's edits to the map, which can be undone. Growth is not among them.
+    pub history: History,
+    growth: Growth,
+    levels: Levels,
+    pub stats: Stats,
+    /// Updates since land value was last surveyed.
+    land_value_ticks: u64,
+    /// Where new residents set off from, if anywhere: the road by the station
+    /// unless told otherwise.
+    pub spawn: Option<Coord2>,
+    passenger_tx: mpsc::Sender<Agent>,
+}
+
+impl Simulation {
+    pub fn new(
+        mut agents: Agents,
+        map: Arc<RwLock<Map>>,
+        passenger_tx: mpsc::Sender<Agent>,
+    ) -> Simulation {
+        agents.watch(&mut map.write().unwrap());
+        let seed = agents.seed();
+        let spawn = station_road(&map.read().unwrap());
+        Simulation {
+            agents,
+            map,
+            history: History::new(HISTORY_LIMIT),
+            growth: Growth::new(GROWTH_TICKS, seed),
+            levels: Levels::new(LEVEL_TICKS),
+            stats: Stats::new(INCOME_TICKS),
+            land_value_ticks: LAND_VALUE_TICKS,
+            spawn,
+            passenger_tx,
+        }
+    }
+
+    /// Grow the map as `Map::expand` does, moving everything on it along. Edits
+    /// made beforehand can no longer be undone.
+    pub fn expand(&mut self, direction: Direction, amount: u64) {
+        let offset = self.map.write().unwrap().expand(direction, amount);
+        self.shift(offset);
+    }
+
+    /// Shrink the map as `Map::shrink` does, moving everything on it along.
+    pub fn shrink(&mut self, direction: Direction, amount: u64) -> Result<(), MapError> {
+        let offset = self.map.write().unwrap().shrink(direction, amount)?;
+        self.shift(offset);
+        Ok(())
+    }
+
+    /// Advance one tick, returning whatever happened worth reporting.
+    pub fn update(&mut self) -> Vec<StatsEvent> {
+        {
+            let map = self.map.read().unwrap();
+            self.agents.update(&map);
+        }
+        self.survey_land_value();
+        self.grow();
+        self.review();
+        self.stats.take_events()
+    }
+
+    /// Write the session as `save_game` does, ending with the map.
+    pub fn save(&mut self, writer: &mut SaveWriter) {
+        self.agents.save(writer);
+        self.growth.save(writer);
+        self.levels.save(writer);
+        self.stats.save(writer);
+        let mut fields = vec!["simulation".to_string(), self.land_value_ticks.to_string()];
+        match self.spawn {
+            Some(spawn) => {
+                fields.push(spawn.x.to_string());
+                fields.push(spawn.y.to_string());
+            }
+            None => fields.push("none".to_string()),
+        }
+        writer.record(&fields);
+        writer.record(&["map"]);
+        writer.text(&self.map.read().unwrap().to_text());
+    }
+
+    /// Read back what `save` wrote. Undo history is not kept.
+    pub fn load(
+        reader: &mut SaveReader,
+        passenger_tx: mpsc::Sender<Agent>,
+    ) -> Result<Simulation, SaveError> {
+        let agents = Agents::load(reader)?;
+        let growth = Growth::load(reader)?;
+        let levels = Levels::load(reader)?;
+        let stats = Stats::load(reader)?;
+        let (line, fields) = reader.expect("simulation")?;
+        let land_value_ticks = reader.parse(line, &fields, 0)?;
+        let spawn = if fields.get(1) == Some(&"none") {
+            None
+        } else {
+            Some(Coord2 {
+                x: reader.parse(line, &fields, 1)?,
+                y: reader.parse(line, &fields, 2)?,
+            })
+        };
+        reader.expect("map")?;
+        let map: Map = reader.rest().parse()?;
+
+        let mut simulation = Simulation::new(agents, Arc::new(RwLock::new(map)), passenger_tx);
+        simulation.growth = growth;
+        simulation.levels = levels;
+        simulation.stats = stats;
+        simulation.land_value_ticks = land_value_ticks;
+        simulation.spawn = spawn;
+        Ok(simulation)
+    }
+
+    /// Where the city stands now.
+    pub fn report(&self) -> Report {
+        let map = self.map.read().unwrap();
+        let mut buildings = BTreeMap::new();
+        for (&building, origins) in &map.buildings {
+            let levels = origins
+                .iter()
+                .filter_map(|origin| map.building_at(*origin))
+                .map(|building_tile| building_tile.level)
+                .sum();
+            buildings.insert(building, (origins.len(), levels));
+        }
+        Report {
+            ticks: self.agents.tick(),
+            residents: self.agents.count(AgentKind::Resident),
+            buildings,
+            funds: self.stats.funds,
+        }
+    }
+
+    /// Survey land value every `LAND_VALUE_TICKS` updates, starting with the first.
+    fn survey_land_value(&mut self) {
+        if self.land_value_ticks < LAND_VALUE_TICKS {
+            self.land_value_ticks += 1;
+            return;
+        }
+        self.land_value_ticks = 0;
+        self.map.write().unwrap().survey_land_value();
+    }
+
+    /// Build whatever growth wants on zoned land, if anything.
+    fn grow(&mut self) {
+        let grown = {
+            let mut map = self.map.write().unwrap();
+            self.growth.update(&map).and_then(|(site, building)| {
+                map.build(site, building).ok()?;
+                Some((site, building, map.capacity(site).unwrap_or(0)))
+            })
+        };
+        if let Some((site, Building::House, places)) = grown {
+            self.moved_in(site, places);
+        }
+    }
+
+    /// Review building levels and collect income, recording whatever happened.
+    fn review(&mut self) {
+        let events = {
+            let mut map = self.map.write().unwrap();
+            let events = self.levels.update(&mut map, self.agents.occupancy());
+            self.stats.update(&map);
+            events
+        };
+        for event in events {
+            // A house which densifies takes in another household.
+            if let StatsEvent::Upgraded(origin, Building::House, _) = event {
+                self.moved_in(origin, Building::House.capacity().unwrap());
+            }
+            self.stats.record(event);
+        }
+    }
+
+    /// Bring residents for `places` in the house at `home`, by train if there is
+    /// one. With nowhere to set off from, they set off from home.
+    pub fn moved_in(&mut self, home: Coord2, places: u64) {
+        let from = self.spawn.unwrap_or(home);
+        let by_train = self.agents.count(AgentKind::Train) > 0;
+        for _ in 0..places {
+            let decider = ResidentDecider::new(home);
+            let agent = Agent::new(from, Box::new(decider));
+            if by_train {
+                self.passenger_tx.send(agent).unwrap();
+            } else {
+                self.agents.insert(AgentKind::Resident, agent);
+            }
+        }
+    }
+
+    fn shift(&mut self, offset: (i64, i64)) {
+        self.agents.shift(offset);
+        self.levels.shift(offset);
+        self.spawn = self.spawn.map(|spawn| spawn.shifted(offset));
+        self.history.clear();
+    }
+}
+
+/// A summary of the city, as printed at the end of a headless run.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    /// Updates since the world began.
+    pub ticks: u64,
+    /// Residents in town, not counting those still on the train.
+    pub residents: usize,
+    /// How many of each building stand, and their levels added together.
+    pub buildings: BTreeMap<Building, (usize, u64)>,
+    pub funds: u64,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "ticks: {}", self.ticks)?;
+        writeln!(f, "residents: {}", self.residents)?;
+        for (building, &(count, levels)) in &self.buildings {
+            writeln!(f, "{}: {} at {} levels", building.name(), count, levels)?;
+        }
+        write!(f, "funds: {}", self.funds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started(seed: u64) -> Simulation {
+        let (passenger_tx, passenger_rx) = mpsc::channel();
+        let settings = TownSettings {
+            seed,
+            ..TownSettings::default()
+        };
+        let agents = Agents::new(8, seed);
+        let (map, agents) = settings
+            .generate(agents, &passenger_tx, passenger_rx)
+            .unwrap();
+        Simulation::new(agents, Arc::new(RwLock::new(map)), passenger_tx)
+    }
+
+    fn run(seed: u64, ticks: u64) -> Report {
+        let mut simulation = started(seed);
+        for _ in 0..ticks {
+            simulation.update();
+        }
+        simulation.report()
+    }
+
+    #[test]
+    fn a_headless_run_reports_the_same_city_for_the_same_seed() {
+        let report = run(11, 400);
+        assert_eq!(report.ticks, 400);
+        assert!(report.residents > 0);
+        assert!(report.funds > 0);
+        assert_eq!(report, run(11, 400));
+    }
+
+    #[test]
+    fn new_residents_set_off_from_wherever_the_station_has_moved_to() {
+        let mut simulation = started(11);
+        let spawn = simulation.spawn.unwrap();
+        simulation.expand(Direction::West, 10);
+        simulation.expand(Direction::North, 5);
+        assert_eq!(simulation.spawn, Some(spawn.shifted((10, 5))));
+        assert_eq!(
+            simulation.spawn,
+            station_road(&simulation.map.read().unwrap())
+        );
+    }
+
+    #[test]
+    fn households_moving_in_later_set_off_from_the_scenario_spawn() {
+        let text = "tilewater-scenario 1\ndimensions 20 20\nroad street 10 3 10 9\nspawn 10 9\n";
+        let scenario: Scenario = text.parse().unwrap();
+        let (passenger_tx, passenger_rx) = mpsc::channel();
+        let (map, agents, spawn) = scenario.start(Agents::new(8, 1), &passenger_tx, passenger_rx);
+        assert_eq!(spawn, Some(Coord2 { x: 10, y: 9 }));
+        let mut simulation = Simulation::new(agents, Arc::new(RwLock::new(map)), passenger_tx);
+        simulation.spawn = spawn;
+
+        let home = Coord2 { x: 12, y: 4 };
+        simulation
+            .map
+            .write()
+            .unwrap()
+            .build(home, Building::House)
+            .unwrap();
+        simulation.moved_in(home, 2);
+        assert_eq!(
+            simulation.agents.agent_subunit_positions()[&AgentKind::Resident],
+            vec![(10.0, 9.0); 2]
+        );
+    }
+
+    #[test]
+    fn funds_and_progress_survive_saving() {
+        let mut simulation = started(11);
+        for _ in 0..450 {
+            simulation.update();
+        }
+        let text = save_game(&mut simulation);
+        let (passenger_tx, passenger_rx) = mpsc::channel();
+        let registry = DeciderRegistry::default();
+        let mut loaded = load_game(&text, &registry, passenger_tx, passenger_rx).unwrap();
+        assert!(loaded.stats.funds > 0);
+        assert_eq!(loaded.report(), simulation.report());
+        assert_eq!(save_game(&mut loaded), text);
+    }
+}
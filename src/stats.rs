@@ -0,0 +1,98 @@
+use super::*;
+
+/// Something worth reporting about the town, as streamed by `Stats`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StatsEvent {
+    /// A building went up to the level given.
+    Upgraded(Coord2, Building, u64),
+    /// A building went down to the level given.
+    Downgraded(Coord2, Building, u64),
+}
+
+impl fmt::Display for StatsEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (l, building, level, change) = match *self {
+            StatsEvent::Upgraded(l, building, level) => (l, building, level, "up"),
+            StatsEvent::Downgraded(l, building, level) => (l, building, level, "down"),
+        };
+        write!(
+            f,
+            "the {} at ({}, {}) went {} to level {}",
+            building.name(),
+            l.x,
+            l.y,
+            change,
+            level
+        )
+    }
+}
+
+/// Running statistics of the town: the income it has collected, and a stream of
+/// `StatsEvent`s for reporting.
+#[derive(Clone, Debug)]
+pub struct Stats {
+    ticks_per_collection: u64,
+    ticks: u64,
+    /// Income collected so far.
+    pub funds: u64,
+    /// Events recorded and not yet taken.
+    events: Vec<StatsEvent>,
+}
+
+impl Stats {
+    /// Statistics which collect income once every `ticks_per_collection` updates.
+    pub fn new(ticks_per_collection: u64) -> Stats {
+        Stats {
+            ticks_per_collection,
+            ticks: 0,
+            funds: 0,
+            events: vec![],
+        }
+    }
+
+    pub fn save(&self, writer: &mut SaveWriter) {
+        writer.record(&[
+            "stats".to_string(),
+            self.ticks_per_collection.to_string(),
+            self.ticks.to_string(),
+            self.funds.to_string(),
+        ]);
+    }
+
+    pub fn load(reader: &mut SaveReader) -> Result<Stats, SaveError> {
+        let (line, fields) = reader.expect("stats")?;
+        let mut stats = Stats::new(reader.parse(line, &fields, 0)?);
+        stats.ticks = reader.parse(line, &fields, 1)?;
+        stats.funds = reader.parse(line, &fields, 2)?;
+        Ok(stats)
+    }
+
+    /// What every building on `map` brings in at each collection, by its level.
+    pub fn income(map: &Map) -> u64 {
+        map.buildings
+            .values()
+            .flat_map(|origins| origins.iter())
+            .filter_map(|origin| map.building_at(*origin))
+            .map(BuildingTile::income)
+            .sum()
+    }
+
+    /// Advance one tick, collecting income when it is due.
+    pub fn update(&mut self, map: &Map) {
+        self.ticks += 1;
+        if self.ticks < self.ticks_per_collection {
+            return;
+        }
+        self.ticks = 0;
+        self.funds += Stats::income(map);
+    }
+
+    pub fn record(&mut self, event: StatsEvent) {
+        self.events.push(event);
+    }
+
+    /// Every event recorded since this was last called, oldest first.
+    pub fn take_events(&mut self) -> Vec<StatsEvent> {
+        self.events.drain(..).collect()
+    }
+}
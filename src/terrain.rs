@@ -0,0 +1,106 @@
+use super::*;
+
+/// The ground beneath any tile. Everywhere is grass unless set otherwise.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Terrain {
+    Grass,
+    /// Only roads and rails can go over water, as bridges. Nobody can walk on it.
+    Water,
+    /// Too thick to build in, and slow to walk through.
+    Forest,
+    /// Too steep for roads and rails, and slow to walk over.
+    Hills,
+}
+
+impl Default for Terrain {
+    fn default() -> Terrain {
+        Terrain::Grass
+    }
+}
+
+impl Terrain {
+    pub fn all() -> [Terrain; 4] {
+        [
+            Terrain::Grass,
+            Terrain::Water,
+            Terrain::Forest,
+            Terrain::Hills,
+        ]
+    }
+
+    pub fn from_code(code: char) -> Option<Terrain> {
+        Terrain::all().iter().cloned().find(|t| t.code() == code)
+    }
+
+    pub fn from_name(name: &str) -> Option<Terrain> {
+        Terrain::all().iter().cloned().find(|t| t.name() == name)
+    }
+
+    pub fn code(&self) -> char {
+        match *self {
+            Terrain::Grass => ' ',
+            Terrain::Water => '~',
+            Terrain::Forest => '*',
+            Terrain::Hills => '^',
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Terrain::Grass => "grass",
+            Terrain::Water => "water",
+            Terrain::Forest => "forest",
+            Terrain::Hills => "hills",
+        }
+    }
+
+    /// Whether buildings and entrances can stand here.
+    pub fn is_buildable(&self) -> bool {
+        match *self {
+            Terrain::Grass | Terrain::Hills => true,
+            Terrain::Water | Terrain::Forest => false,
+        }
+    }
+
+    /// Whether roads can be laid here. Over water they form a bridge.
+    pub fn is_pavable(&self) -> bool {
+        *self != Terrain::Hills
+    }
+
+    /// Whether rails can be laid here. Over water they form a bridge.
+    pub fn is_railable(&self) -> bool {
+        *self != Terrain::Hills
+    }
+
+    /// Whether agents can walk here off-road.
+    pub fn is_walkable(&self) -> bool {
+        *self != Terrain::Water
+    }
+
+    /// How much slower walking here is than walking over grass.
+    pub fn walking_cost(&self) -> f64 {
+        match *self {
+            Terrain::Grass => 1.0,
+            Terrain::Forest => 2.0,
+            Terrain::Hills => 1.5,
+            Terrain::Water => ::std::f64::INFINITY,
+        }
+    }
+
+    /// Whether `tile` can stand on this ground.
+    pub fn supports(&self, tile: &Tile) -> bool {
+        match *tile {
+            Tile::Building(_) | Tile::Entrance(_) => self.is_buildable(),
+            Tile::Paving(_) => self.is_pavable(),
+            Tile::Rails(ref rails_tile) => {
+                self.is_railable() && (!rails_tile.is_station() || self.is_buildable())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Terrain {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -1,10 +1,29 @@
 use super::*;
-use std::collections::*;
 
+/// The highest level a building can be raised to.
+pub const MAX_BUILDING_LEVEL: u64 = 3;
+
+/// One tile of a building's footprint.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct BuildingTile {
     pub building: Building,
     pub entryway_pos: Coord2,
+    /// The north-west corner of the footprint, where the building is indexed.
+    pub origin: Coord2,
+    /// From 1 to `MAX_BUILDING_LEVEL`, the same across the footprint.
+    pub level: u64,
+}
+
+impl BuildingTile {
+    /// How many residents the building has places for at its level.
+    pub fn capacity(&self) -> Option<u64> {
+        self.building.capacity().map(|capacity| capacity * self.level)
+    }
+
+    /// What the building brings in at its level, every time income is collected.
+    pub fn income(&self) -> u64 {
+        self.building.income() * self.level
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -16,15 +35,223 @@ pub struct EntranceTile {
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct PavingTile {
-    pub entryways_pos: HashSet<Coord2>,
-    pub pavings_pos: HashSet<Coord2>,
+    /// Sides with an entrance attached to this paving.
+    pub entryways: Neighbours,
+    /// Sides with another paving.
+    pub pavings: Neighbours,
+    pub class: RoadClass,
+}
+
+/// How much traffic a road is built for. Better roads are quicker to travel along,
+/// so routes prefer them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RoadClass {
+    Footpath,
+    Street,
+    Avenue,
+}
+
+impl Default for RoadClass {
+    fn default() -> RoadClass {
+        RoadClass::Street
+    }
+}
+
+impl RoadClass {
+    pub fn all() -> [RoadClass; 3] {
+        [RoadClass::Footpath, RoadClass::Street, RoadClass::Avenue]
+    }
+
+    pub fn from_code(code: char) -> Option<RoadClass> {
+        RoadClass::all().iter().cloned().find(|c| c.code() == code)
+    }
+
+    pub fn from_name(name: &str) -> Option<RoadClass> {
+        RoadClass::all().iter().cloned().find(|c| c.name() == name)
+    }
+
+    pub fn code(&self) -> char {
+        match *self {
+            RoadClass::Footpath => '.',
+            RoadClass::Street => ':',
+            RoadClass::Avenue => '#',
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            RoadClass::Footpath => "footpath",
+            RoadClass::Street => "street",
+            RoadClass::Avenue => "avenue",
+        }
+    }
+
+    /// The class above this one, if any.
+    pub fn upgraded(&self) -> Option<RoadClass> {
+        match *self {
+            RoadClass::Footpath => Some(RoadClass::Street),
+            RoadClass::Street => Some(RoadClass::Avenue),
+            RoadClass::Avenue => None,
+        }
+    }
+
+    /// The class below this one, if any.
+    pub fn downgraded(&self) -> Option<RoadClass> {
+        match *self {
+            RoadClass::Footpath => None,
+            RoadClass::Street => Some(RoadClass::Footpath),
+            RoadClass::Avenue => Some(RoadClass::Street),
+        }
+    }
+
+    /// The cost of routing onto a road of this class.
+    pub fn cost(&self) -> f64 {
+        match *self {
+            RoadClass::Footpath => 2.0,
+            RoadClass::Street => 1.0,
+            RoadClass::Avenue => 0.5,
+        }
+    }
+
+    /// How many times faster than along a street agents move along this class.
+    pub fn speed(&self) -> f64 {
+        1.0 / self.cost()
+    }
+}
+
+impl fmt::Display for RoadClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct RailsTile {
-    pub is_station: bool,
-    pub orientation: Orientation,
-    pub rails_pos: HashSet<Coord2>,
+    /// For a station, its part of the station laid over these rails.
+    pub station: Option<BuildingTile>,
+    pub shape: RailShape,
+    /// Sides with other rails.
+    pub rails: Neighbours,
+}
+
+impl RailsTile {
+    pub fn is_station(&self) -> bool {
+        self.station.is_some()
+    }
+}
+
+/// A set of the sides of a tile, packed into a byte so that tiles stay small.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Neighbours(u8);
+
+impl Neighbours {
+    pub fn new() -> Neighbours {
+        Neighbours(0)
+    }
+
+    fn bit(direction: Direction) -> u8 {
+        match direction {
+            Direction::North => 1,
+            Direction::East => 2,
+            Direction::South => 4,
+            Direction::West => 8,
+        }
+    }
+
+    pub fn insert(&mut self, direction: Direction) {
+        self.0 |= Neighbours::bit(direction);
+    }
+
+    pub fn remove(&mut self, direction: Direction) {
+        self.0 &= !Neighbours::bit(direction);
+    }
+
+    pub fn contains(&self, direction: Direction) -> bool {
+        self.0 & Neighbours::bit(direction) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    /// The sides in the set, in `Direction::all()` order.
+    pub fn directions(&self) -> Vec<Direction> {
+        Direction::all()
+            .iter()
+            .cloned()
+            .filter(|d| self.contains(*d))
+            .collect()
+    }
+
+    /// The locations on each side in the set of a tile at `location`.
+    pub fn locations(&self, location: Coord2) -> Vec<Coord2> {
+        self.directions()
+            .into_iter()
+            .filter_map(|d| location.step(d))
+            .collect()
+    }
+}
+
+/// How a rails tile joins onto the rails around it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RailShape {
+    /// Straight through along this orientation. Also used for dead ends and
+    /// unconnected rails.
+    Straight(Orientation),
+    /// A bend joining these two sides, in `Direction::all()` order.
+    Corner(Direction, Direction),
+    /// A T junction joining every side except this one.
+    Junction(Direction),
+    /// A crossing joining all four sides.
+    Crossing,
+}
+
+impl RailShape {
+    /// The shape of rails joined on the sides in `connections`. Unconnected rails
+    /// keep the orientation `lone`.
+    pub fn from_connections(connections: &[Direction], lone: Orientation) -> RailShape {
+        let sides: Vec<Direction> = Direction::all()
+            .iter()
+            .cloned()
+            .filter(|d| connections.contains(d))
+            .collect();
+        match sides.len() {
+            0 => RailShape::Straight(lone),
+            1 => RailShape::Straight(sides[0].orientation()),
+            2 if sides[0].orientation() == sides[1].orientation() => {
+                RailShape::Straight(sides[0].orientation())
+            }
+            2 => RailShape::Corner(sides[0], sides[1]),
+            3 => {
+                let missing = Direction::all()
+                    .iter()
+                    .cloned()
+                    .find(|d| !sides.contains(d))
+                    .unwrap();
+                RailShape::Junction(missing)
+            }
+            _ => RailShape::Crossing,
+        }
+    }
+
+    /// The sides of the tile that rails run out to.
+    pub fn directions(&self) -> Vec<Direction> {
+        match *self {
+            RailShape::Straight(Orientation::Vertical) => vec![Direction::North, Direction::South],
+            RailShape::Straight(Orientation::Horizontal) => vec![Direction::East, Direction::West],
+            RailShape::Corner(a, b) => vec![a, b],
+            RailShape::Junction(missing) => Direction::all()
+                .iter()
+                .cloned()
+                .filter(|d| *d != missing)
+                .collect(),
+            RailShape::Crossing => Direction::all().to_vec(),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -92,6 +319,26 @@ impl Tile {
             _ => None,
         }
     }
+
+    /// Move every location this tile refers to by `offset`, as when the map it is on
+    /// is resized.
+    pub fn shift(&mut self, offset: (i64, i64)) {
+        match *self {
+            Tile::Building(ref mut building_tile)
+            | Tile::Rails(RailsTile {
+                station: Some(ref mut building_tile),
+                ..
+            }) => {
+                building_tile.entryway_pos = building_tile.entryway_pos.shifted(offset);
+                building_tile.origin = building_tile.origin.shifted(offset);
+            }
+            Tile::Entrance(ref mut entrance_tile) => {
+                entrance_tile.road_pos = entrance_tile.road_pos.shifted(offset);
+                entrance_tile.building_pos = entrance_tile.building_pos.shifted(offset);
+            }
+            Tile::Paving(_) | Tile::Rails(_) => {}
+        }
+    }
 }
 
 impl fmt::Display for Tile {
@@ -102,10 +349,14 @@ impl fmt::Display for Tile {
                 Orientation::Vertical => write!(f, "|"),
                 Orientation::Horizontal => write!(f, "-"),
             },
-            Tile::Paving(PavingTile { .. }) => write!(f, ":"),
-            Tile::Rails(RailsTile { orientation, .. }) => match orientation {
-                Orientation::Vertical => write!(f, "‖"),
-                Orientation::Horizontal => write!(f, "="),
+            Tile::Paving(PavingTile { class, .. }) => write!(f, "{}", class.code()),
+            Tile::Rails(RailsTile {
+                station: Some(_), ..
+            }) => write!(f, "{}", Building::TrainStation),
+            Tile::Rails(RailsTile { shape, .. }) => match shape {
+                RailShape::Straight(Orientation::Vertical) => write!(f, "‖"),
+                RailShape::Straight(Orientation::Horizontal) => write!(f, "="),
+                _ => write!(f, "+"),
             },
         }
     }
@@ -0,0 +1,63 @@
+use super::*;
+
+/// What kind of building may grow on a tile by itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Zone {
+    Residential,
+    Commercial,
+    Industrial,
+}
+
+impl Zone {
+    pub fn all() -> [Zone; 3] {
+        [Zone::Residential, Zone::Commercial, Zone::Industrial]
+    }
+
+    pub fn from_key(key: Key) -> Option<Zone> {
+        match key {
+            Key::D1 => Some(Zone::Residential),
+            Key::D2 => Some(Zone::Commercial),
+            Key::D3 => Some(Zone::Industrial),
+            _ => None,
+        }
+    }
+
+    pub fn from_code(code: char) -> Option<Zone> {
+        Zone::all().iter().cloned().find(|z| z.code() == code)
+    }
+
+    pub fn from_name(name: &str) -> Option<Zone> {
+        Zone::all().iter().cloned().find(|z| z.name() == name)
+    }
+
+    pub fn code(&self) -> char {
+        match *self {
+            Zone::Residential => 'R',
+            Zone::Commercial => 'C',
+            Zone::Industrial => 'I',
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Zone::Residential => "residential",
+            Zone::Commercial => "commercial",
+            Zone::Industrial => "industrial",
+        }
+    }
+
+    /// The buildings which grow in this zone.
+    pub fn buildings(&self) -> Vec<Building> {
+        match *self {
+            Zone::Residential => vec![Building::House],
+            Zone::Commercial => vec![Building::GeneralStore, Building::Saloon],
+            Zone::Industrial => vec![Building::Factory],
+        }
+    }
+}
+
+impl fmt::Display for Zone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}